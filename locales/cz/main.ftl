@@ -3,6 +3,28 @@ docx-filler-fail-overwrite =
     Soubor \" { $filename } \" již existuje!
     Raději nebudu nic přepisovat!
 docx-filler-fail-load = Nepovedlo se načíst obsah DOCX šablony!
+# $block (String) - Marker of the conditional block, e.g. {{#if NAME}}.
+docx-filler-block-unbalanced = Značka podmíněného bloku { $block } nemá svůj začátek/konec!
+# $block (String) - Marker of the conditional block, e.g. {{#if NAME}}.
+docx-filler-block-structure =
+    Podmíněný blok { $block } musí skončit ve stejné části dokumentu, ve které začíná
+    (např. ve stejné buňce tabulky, nebo mimo tabulku).
+# $marker (String) - Marker of the repeated table rows, e.g. {{#each items}}.
+docx-filler-loop-unbalanced = Značka opakovaných řádek { $marker } nemá svůj začátek/konec!
+# $marker (String) - Marker of the repeated table rows, e.g. {{#each items}}.
+docx-filler-loop-no-row = Opakované řádky { $marker } musí začínat i končit v řádcích stejné tabulky!
+# $token (String) - Token with the unknown formatter, e.g. {{NAME|bold}}.
+docx-filler-unknown-format = Proměnná { $token } používá neznámý formát, nebo formát s neplatným parametrem!
+# $path (String) - Path to the image file.
+docx-filler-image-load = Nepovedlo se načíst obrázek "{ $path }"!
+# $path (String) - Path to the image file.
+docx-filler-image-format = Soubor "{ $path }" není podporovaný obrázek (PNG, JPEG, GIF nebo BMP)!
+# $open (String) - Text opening each token, e.g. {{.
+# $close (String) - Text closing each token, e.g. }}.
+docx-filler-invalid-delimiters = Oddělovače proměnných "{ $open }" a "{ $close }" nelze použít! Nesmí být prázdné, ani obsahovat znaky < > & " '.
+# $name (String) - Name of the document property.
+docx-filler-unknown-property = Vlastnost dokumentu "{ $name }" není v šabloně definována! Vlastní vlastnosti je nutné nejdříve přidat do šablony.
+docx-filler-part-not-filled = Část dokumentu "{ $part }" nebyla vyplněna, vytvořený dokument by nebyl úplný!
 
 valid-no-tokens = Ve výbraném souboru nejsou žádné proměnné!
 # $token (String) - The token that user tried to use multiple times in replacements.
@@ -14,6 +36,10 @@ valid-count-mismatch =
     Počet žetonů a počet hodnot pro vyplnění nesedí (není stejný)!
     proměnné: { $tokens }
     hodnoty: { $values }
+# $token (String) - The token with declared type of value.
+# $value (String) - The value that does not match the type.
+# $type (String) - The type declared for the token, e.g. int, decimal, date or email.
+valid-type-mismatch = Hodnota "{ $value }" proměnné { $token } neodpovídá typu { $type }!
 # $line (String) - Number of input line where the problem is.
 # $details (String) - Problem details.
 valid-line-mismatch =
@@ -41,6 +67,33 @@ ui-template-label = Načtená šablona:
 ui-template-dialog = Otevřít soubor
 ui-template-button = Načíst novou
 ui-template-default-folder-fail = Nepovedlo se otevřít pracovní adresář!
+ui-template-report-button = Přehled proměnných
+ui-report-title = Proměnné v šabloně
+# $token (String) - The token reported.
+# $count (String) - Number of the token occurrences in the template.
+ui-report-token = { $token } - nalezeno { $count }x
+# $paragraph (String) - Sequential number of the paragraph containing the token.
+ui-report-paragraph = odstavec { $paragraph }
+ui-report-header = záhlaví/zápatí
+ui-report-table = tabulka
+ui-report-text-box = textové pole
+ui-lint-title = Možné problémy v šabloně
+# $token (String) - The problematic text of the template.
+ui-lint-unclosed = { $token } - proměnná není uzavřená
+# $token (String) - The problematic text of the template.
+ui-lint-unopened = { $token } - uzavírací oddělovač bez otevíracího
+# $token (String) - The problematic text of the template.
+ui-lint-split = { $token } - proměnná je rozdělená jiným obsahem (konec odstavce, pole, ...)
+# $token (String) - The problematic text of the template.
+ui-lint-markup = { $token } - proměnná obsahuje XML značky
+# $token (String) - The problematic text of the template.
+ui-lint-long = { $token } - proměnná je podezřele dlouhá
+# $token (String) - The problematic text of the template.
+# $similar (String) - The other token, differing just by whitespace or letter case.
+ui-lint-similar = { $token } - proměnná je skoro stejná jako { $similar }
+# $token (String) - The problematic text of the template.
+# $default (String) - The default value declared for the token first.
+ui-lint-default = { $token } - proměnná má jinou výchozí hodnotu než dříve uvedenou { $default }, která se použije
 #
 ui-tokens-label = Proměnné nalezené v šabloně:
 ui-tokens-failed-sep-create = Selhalo vytváření oddělovače!
@@ -52,6 +105,14 @@ ui-values-label = Hodnoty pro nahrazení proměnných v šabloně (co řádek, t
 #
 ui-output-label = Pravidlo pro jméno nových souborů:
 ui-output-button = Vytvořit DOCX
+ui-output-combine-check = Spojit do jednoho dokumentu
+ui-output-transactional-check = Všechno, nebo nic
 ui-options-sep-label = Oddělovač hodnot:
+ui-options-delimiters-label = Oddělovače proměnných:
+ui-options-partial-check = Ponechat nevyplněné proměnné
+ui-options-remove-controls-check = Odstranit ovládací prvky obsahu
+ui-options-ignore-case-check = Ignorovat velikost písmen v proměnných
+ui-options-unicode-check = Sjednotit Unicode tvary proměnných
+ui-options-workers-label = Souběžně generované dokumenty:
 
 lang-not-found = Nelze změtnit nastavení na daný jazyk!
@@ -3,6 +3,28 @@ docx-filler-fail-overwrite =
     File \" { $filename } \" already exists!
     I will not overwrite anything!
 docx-filler-fail-load = Failed to read DOCX template contents!
+# $block (String) - Marker of the conditional block, e.g. {{#if NAME}}.
+docx-filler-block-unbalanced = Conditional block marker { $block } does not have its opening/closing counterpart!
+# $block (String) - Marker of the conditional block, e.g. {{#if NAME}}.
+docx-filler-block-structure =
+    Conditional block { $block } has to end in the same part of the document it starts in
+    (e.g. in the same table cell, or outside of any table).
+# $marker (String) - Marker of the repeated table rows, e.g. {{#each items}}.
+docx-filler-loop-unbalanced = Repeated rows marker { $marker } does not have its opening/closing counterpart!
+# $marker (String) - Marker of the repeated table rows, e.g. {{#each items}}.
+docx-filler-loop-no-row = Repeated rows { $marker } have to start and end in rows of the same table!
+# $token (String) - Token with the unknown formatter, e.g. {{NAME|bold}}.
+docx-filler-unknown-format = Token { $token } uses unknown formatter, or formatter with invalid argument!
+# $path (String) - Path to the image file.
+docx-filler-image-load = Image file "{ $path }" cannot be read!
+# $path (String) - Path to the image file.
+docx-filler-image-format = File "{ $path }" is not a supported image (PNG, JPEG, GIF or BMP)!
+# $open (String) - Text opening each token, e.g. {{.
+# $close (String) - Text closing each token, e.g. }}.
+docx-filler-invalid-delimiters = Token delimiters "{ $open }" and "{ $close }" cannot be used! They must not be empty, nor contain any of < > & " ' characters.
+# $name (String) - Name of the document property.
+docx-filler-unknown-property = Document property "{ $name }" is not defined in the template! Custom properties have to be added to the template first.
+docx-filler-part-not-filled = Document part "{ $part }" was not filled, the generated document would be incomplete!
 
 valid-no-tokens = No tokens found!
 # $token (String) - The token that user tried to use multiple times in replacements.
@@ -14,6 +36,10 @@ valid-count-mismatch =
     Number of tokens is not equal to number of values on input!
     tokens: { $tokens }
     values: { $values }
+# $token (String) - The token with declared type of value.
+# $value (String) - The value that does not match the type.
+# $type (String) - The type declared for the token, e.g. int, decimal, date or email.
+valid-type-mismatch = Value "{ $value }" of token { $token } is not a valid { $type }!
 # $line (String) - Number of input line where the problem is.
 # $details (String) - Problem details.
 valid-line-mismatch =
@@ -41,6 +67,33 @@ ui-template-label = Currently loaded template:
 ui-template-dialog = Open File
 ui-template-button = Load new docx
 ui-template-default-folder-fail = Failed to set working folder!
+ui-template-report-button = Token report
+ui-report-title = Tokens in template
+# $token (String) - The token reported.
+# $count (String) - Number of the token occurrences in the template.
+ui-report-token = { $token } - found { $count }x
+# $paragraph (String) - Sequential number of the paragraph containing the token.
+ui-report-paragraph = paragraph { $paragraph }
+ui-report-header = header/footer
+ui-report-table = table
+ui-report-text-box = text box
+ui-lint-title = Possible problems in template
+# $token (String) - The problematic text of the template.
+ui-lint-unclosed = { $token } - token is not closed
+# $token (String) - The problematic text of the template.
+ui-lint-unopened = { $token } - closing delimiter without the opening one
+# $token (String) - The problematic text of the template.
+ui-lint-split = { $token } - token is split by other contents (paragraph break, field, ...)
+# $token (String) - The problematic text of the template.
+ui-lint-markup = { $token } - token contains XML markup
+# $token (String) - The problematic text of the template.
+ui-lint-long = { $token } - token is suspiciously long
+# $token (String) - The problematic text of the template.
+# $similar (String) - The other token, differing just by whitespace or letter case.
+ui-lint-similar = { $token } - token is almost the same as { $similar }
+# $token (String) - The problematic text of the template.
+# $default (String) - The default value declared for the token first.
+ui-lint-default = { $token } - token declares other default value than { $default } declared before, which is used
 #
 ui-tokens-label = Tokens (placeholders) found in file:
 ui-tokens-failed-sep-create = Failed to create separator!
@@ -52,6 +105,14 @@ ui-values-label = Values to be filled over tokens (one line per docx to create):
 #
 ui-output-label = Output files name pattern:
 ui-output-button = Generate DOCX files
+ui-output-combine-check = Combine into one document
+ui-output-transactional-check = All or nothing
 ui-options-sep-label = Value separator:
+ui-options-delimiters-label = Token delimiters:
+ui-options-partial-check = Keep unfilled tokens
+ui-options-remove-controls-check = Remove content controls
+ui-options-ignore-case-check = Ignore letter case in tokens
+ui-options-unicode-check = Unify Unicode forms of tokens
+ui-options-workers-label = Parallel documents:
 
 lang-not-found = Cannot switch to requested language!
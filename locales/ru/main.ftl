@@ -3,6 +3,28 @@ docx-filler-fail-overwrite =
     Файл \"{ $filename }\" уже существует!
     Я не буду его переписывать!
 docx-filler-fail-load = Не удалось прочитать содержимое DOCX шаблона!
+# $block (String) - Marker of the conditional block, e.g. {{#if NAME}}.
+docx-filler-block-unbalanced = У метки условного блока { $block } нет парного начала/конца!
+# $block (String) - Marker of the conditional block, e.g. {{#if NAME}}.
+docx-filler-block-structure =
+    Условный блок { $block } должен заканчиваться в той же части документа, где начинается
+    (например, в той же ячейке таблицы или вне таблицы).
+# $marker (String) - Marker of the repeated table rows, e.g. {{#each items}}.
+docx-filler-loop-unbalanced = У метки повторяемых строк { $marker } нет парного начала/конца!
+# $marker (String) - Marker of the repeated table rows, e.g. {{#each items}}.
+docx-filler-loop-no-row = Повторяемые строки { $marker } должны начинаться и заканчиваться в строках одной таблицы!
+# $token (String) - Token with the unknown formatter, e.g. {{NAME|bold}}.
+docx-filler-unknown-format = Переменная { $token } использует неизвестный формат или формат с неверным параметром!
+# $path (String) - Path to the image file.
+docx-filler-image-load = Не удалось прочитать изображение "{ $path }"!
+# $path (String) - Path to the image file.
+docx-filler-image-format = Файл "{ $path }" не является поддерживаемым изображением (PNG, JPEG, GIF или BMP)!
+# $open (String) - Text opening each token, e.g. {{.
+# $close (String) - Text closing each token, e.g. }}.
+docx-filler-invalid-delimiters = Разделители переменных "{ $open }" и "{ $close }" нельзя использовать! Они не должны быть пустыми или содержать символы < > & " '.
+# $name (String) - Name of the document property.
+docx-filler-unknown-property = Свойство документа "{ $name }" не определено в шаблоне! Пользовательские свойства необходимо сначала добавить в шаблон.
+docx-filler-part-not-filled = Часть документа "{ $part }" не была заполнена, созданный документ был бы неполным!
 
 valid-no-tokens = В выбранном файлн не найдены переменные!
 # $token (String) - The token that user tried to use multiple times in replacements.
@@ -14,6 +36,10 @@ valid-count-mismatch =
     Количество переменных не равно количеству готовых значений!
     переменные: { $tokens }
     готовые значения: { $values }
+# $token (String) - The token with declared type of value.
+# $value (String) - The value that does not match the type.
+# $type (String) - The type declared for the token, e.g. int, decimal, date or email.
+valid-type-mismatch = Значение "{ $value }" переменной { $token } не соответствует типу { $type }!
 # $line (String) - Number of input line where the problem is.
 # $details (String) - Problem details.
 valid-line-mismatch =
@@ -41,6 +67,33 @@ ui-template-label = Текущий загруженный шаблон:
 ui-template-dialog = Открыть файл
 ui-template-button = Загрузить новый
 ui-template-default-folder-fail = Не удалось открыть рабочую папку!
+ui-template-report-button = Обзор переменных
+ui-report-title = Переменные в шаблоне
+# $token (String) - The token reported.
+# $count (String) - Number of the token occurrences in the template.
+ui-report-token = { $token } - найдено { $count }x
+# $paragraph (String) - Sequential number of the paragraph containing the token.
+ui-report-paragraph = абзац { $paragraph }
+ui-report-header = колонтитул
+ui-report-table = таблица
+ui-report-text-box = текстовое поле
+ui-lint-title = Возможные проблемы в шаблоне
+# $token (String) - The problematic text of the template.
+ui-lint-unclosed = { $token } - переменная не закрыта
+# $token (String) - The problematic text of the template.
+ui-lint-unopened = { $token } - закрывающий разделитель без открывающего
+# $token (String) - The problematic text of the template.
+ui-lint-split = { $token } - переменная разделена другим содержимым (конец абзаца, поле, ...)
+# $token (String) - The problematic text of the template.
+ui-lint-markup = { $token } - переменная содержит XML разметку
+# $token (String) - The problematic text of the template.
+ui-lint-long = { $token } - переменная подозрительно длинная
+# $token (String) - The problematic text of the template.
+# $similar (String) - The other token, differing just by whitespace or letter case.
+ui-lint-similar = { $token } - переменная почти совпадает с { $similar }
+# $token (String) - The problematic text of the template.
+# $default (String) - The default value declared for the token first.
+ui-lint-default = { $token } - переменная объявляет другое значение по умолчанию, чем ранее объявленное { $default }, которое используется
 #
 ui-tokens-label = В файле найдены следующие переменные (заполнители):
 ui-tokens-failed-sep-create = Не удалось создать разделитель!
@@ -52,6 +105,14 @@ ui-values-label = Новые значения переменных (каждая
 #
 ui-output-label = Шаблон имени выходных файлов:
 ui-output-button = Создать DOCX
+ui-output-combine-check = Объединить в один документ
+ui-output-transactional-check = Всё или ничего
 ui-options-sep-label = Разделитель готовых значений:
+ui-options-delimiters-label = Разделители переменных:
+ui-options-partial-check = Оставить незаполненные переменные
+ui-options-remove-controls-check = Удалить элементы управления содержимым
+ui-options-ignore-case-check = Игнорировать регистр в переменных
+ui-options-unicode-check = Унифицировать формы Unicode в переменных
+ui-options-workers-label = Параллельно создаваемые документы:
 
 lang-not-found = Невозможно переключиться на запрошенный язык!
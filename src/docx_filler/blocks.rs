@@ -0,0 +1,365 @@
+use super::delimiters::Delimiters;
+use super::{runs, xml, DocxError, DocxResult, TokenPackArg, ValuePackArg};
+use crate::lang;
+use regex::Regex;
+use std::ops::Range;
+
+lazy_static! {
+    /// Matches text (between the delimiters) of opening (`{{#if NAME}}`, `{{#unless NAME}}`)
+    /// and closing (`{{/if}}`, `{{/unless}}`) markers of the conditional blocks.
+    static ref MARKER_RE: Regex =
+        Regex::new(r"^(?:#(if|unless)\s+(.*?)\s*|/(if|unless))$").unwrap();
+}
+
+/// Opening or closing marker of a conditional block, found in the document.
+#[derive(Debug, Clone)]
+struct Marker {
+    /// kind of the block - `if` or `unless`.
+    kind: String,
+    /// token deciding about the block contents, for opening markers only.
+    condition: Option<String>,
+    /// byte range of the marker in the document.
+    range: Range<usize>,
+}
+
+/// Returns the token that decides about the conditional block, if the input token is its opening marker
+/// (e.g. `{{VIP}}` for `{{#if VIP}}`).
+pub fn condition_token(token: &str, delimiters: &Delimiters) -> Option<String> {
+    let marker = parse_marker(token, 0..token.len(), delimiters)?;
+    marker.condition
+}
+
+/// Checks whether the input token is a marker of a conditional block (opening or closing one).
+pub fn is_marker(token: &str, delimiters: &Delimiters) -> bool {
+    parse_marker(token, 0..token.len(), delimiters).is_some()
+}
+
+/// Verifies that all the conditional blocks of the document are properly opened and closed.
+///
+/// # Errors
+///
+/// Can return Docx::Processing on failure, with details in message.
+pub fn validate_blocks(xml: &str, delimiters: &Delimiters) -> DocxResult<()> {
+    let mut markers = markers(xml, delimiters);
+    while let Some((open, close)) = innermost_block(xml, &markers)? {
+        markers.retain(|marker| marker.range != open.range && marker.range != close.range);
+    }
+    Ok(())
+}
+
+/// Keeps or removes the conditional blocks of the document, depending on the values of their tokens.
+/// Contents of the `{{#if NAME}}...{{/if}}` block are kept when the value of `{{NAME}}` token is set,
+/// while contents of the `{{#unless NAME}}...{{/unless}}` block are kept when it is not.
+/// Block markers are removed - together with their paragraphs, if they are alone in them.
+/// Blocks of tokens left out of the tokens to be replaced are kept as they are, markers included.
+///
+/// # Arguments
+///
+/// * `xml` - contents of the document part
+/// * `delimiters` - delimiters of the tokens in the template
+/// * `tokens` - vector of tokens to be replaced
+/// * `values` - vector of values to be filled in place of tokens
+///
+/// # Errors
+///
+/// Can return Docx::Processing on improperly placed blocks, with details in message.
+pub fn resolve_conditions(
+    xml: &str,
+    delimiters: &Delimiters,
+    tokens: TokenPackArg,
+    values: ValuePackArg,
+) -> DocxResult<String> {
+    let mut output = xml.to_string();
+    while let Some((open, close)) = innermost_block(&output, &markers(&output, delimiters))? {
+        let condition = open.condition.clone().unwrap_or_default();
+        output = match tokens.iter().position(|token| *token == condition) {
+            Some(i) => {
+                let keep = is_truthy(&values[i].text()) == (open.kind == "if");
+                resolve_block(&output, &open, &close, keep)?
+            }
+            None => hide_block(&output, &open, &close, delimiters),
+        };
+    }
+    Ok(delimiters.reveal(&output))
+}
+
+/// Hides the markers of the block kept as it is from the further search for markers (see `Delimiters::hide`).
+fn hide_block(xml: &str, open: &Marker, close: &Marker, delimiters: &Delimiters) -> String {
+    [
+        &xml[..open.range.start],
+        &delimiters.hide(&xml[open.range.clone()]),
+        &xml[open.range.end..close.range.start],
+        &delimiters.hide(&xml[close.range.clone()]),
+        &xml[close.range.end..],
+    ]
+    .concat()
+}
+
+/// Decides whether the value enables the `if` conditional block - any value apart from
+/// the empty one, `0`, `false` or `no` does.
+fn is_truthy(value: &str) -> bool {
+    let value = value.trim().to_lowercase();
+    !(value.is_empty() || value == "0" || value == "false" || value == "no")
+}
+
+/// Lists all the conditional block markers of the document, in the document order.
+fn markers(xml: &str, delimiters: &Delimiters) -> Vec<Marker> {
+    delimiters
+        .token_re()
+        .find_iter(xml)
+        .filter_map(|found| parse_marker(found.as_str(), found.range(), delimiters))
+        .collect()
+}
+
+/// Parses the conditional block marker text, found at specified range of the document.
+fn parse_marker(text: &str, range: Range<usize>, delimiters: &Delimiters) -> Option<Marker> {
+    let cap = MARKER_RE.captures(delimiters.inner(text))?;
+    match (cap.get(1), cap.get(2), cap.get(3)) {
+        (Some(kind), Some(name), _) => Some(Marker {
+            kind: kind.as_str().to_string(),
+            condition: Some(delimiters.wrap(name.as_str())),
+            range,
+        }),
+        (_, _, Some(kind)) => Some(Marker {
+            kind: kind.as_str().to_string(),
+            condition: None,
+            range,
+        }),
+        _ => None,
+    }
+}
+
+/// Finds the first innermost block (opening & closing marker pair without any other block inside).
+///
+/// # Errors
+///
+/// Can return Docx::Processing if the markers are not paired properly, with details in message.
+fn innermost_block(xml: &str, markers: &[Marker]) -> DocxResult<Option<(Marker, Marker)>> {
+    let mut open: Option<&Marker> = None;
+    for marker in markers {
+        if marker.condition.is_some() {
+            open = Some(marker);
+            continue;
+        }
+        return match open {
+            Some(open) if open.kind == marker.kind => Ok(Some((open.clone(), marker.clone()))),
+            _ => Err(unbalanced_error(xml, marker)),
+        };
+    }
+    match open {
+        Some(open) => Err(unbalanced_error(xml, open)),
+        None => Ok(None),
+    }
+}
+
+/// Creates error for the block marker that does not have its counterpart.
+fn unbalanced_error(xml: &str, marker: &Marker) -> DocxError {
+    let args: lang::TrArgVec = vec![("block".to_string(), xml[marker.range.clone()].to_string())];
+    DocxError::Processing(lang::tr_with_args("docx-filler-block-unbalanced", &args))
+}
+
+/// Keeps or removes contents of a single conditional block, removing its markers.
+///
+/// # Errors
+///
+/// Can return Docx::Processing if the block is not placed properly, with details in message.
+fn resolve_block(xml: &str, open: &Marker, close: &Marker, keep: bool) -> DocxResult<String> {
+    let open_paragraph = xml::enclosing_element(xml, &open.range, "w:p");
+    let close_paragraph = xml::enclosing_element(xml, &close.range, "w:p");
+
+    match (open_paragraph, close_paragraph) {
+        (Some(open_paragraph), Some(close_paragraph)) if open_paragraph != close_paragraph => {
+            if !xml::is_balanced(&xml[open_paragraph.end..close_paragraph.start]) {
+                let args: lang::TrArgVec =
+                    vec![("block".to_string(), xml[open.range.clone()].to_string())];
+                let msg = lang::tr_with_args("docx-filler-block-structure", &args);
+                return Err(DocxError::Processing(msg));
+            }
+            Ok(resolve_paragraphs_block(
+                xml,
+                (open, &open_paragraph),
+                (close, &close_paragraph),
+                keep,
+            ))
+        }
+        (Some(paragraph), Some(_)) => Ok(resolve_inline_block(
+            xml,
+            open,
+            close,
+            Some(&paragraph),
+            keep,
+        )),
+        _ => Ok(resolve_inline_block(xml, open, close, None, keep)),
+    }
+}
+
+/// Resolves the conditional block placed within a single paragraph (or outside of paragraphs).
+/// Paragraph is removed as a whole, if its whole text is the removed block.
+fn resolve_inline_block(
+    xml: &str,
+    open: &Marker,
+    close: &Marker,
+    paragraph: Option<&Range<usize>>,
+    keep: bool,
+) -> String {
+    if keep {
+        return [
+            &xml[..open.range.start],
+            &xml[open.range.end..close.range.start],
+            &xml[close.range.end..],
+        ]
+        .concat();
+    }
+
+    if let Some(paragraph) = paragraph {
+        let outside_block = [
+            &xml[paragraph.start..open.range.start],
+            &xml[close.range.end..paragraph.end],
+        ]
+        .concat();
+        if runs::visible_text(&outside_block).trim().is_empty() {
+            return [&xml[..paragraph.start], &xml[paragraph.end..]].concat();
+        }
+    }
+
+    [
+        &xml[..open.range.start],
+        &runs::strip_text(xml, open.range.start..close.range.end),
+        &xml[close.range.end..],
+    ]
+    .concat()
+}
+
+/// Resolves the conditional block spanning several paragraphs (and whatever is placed between them).
+fn resolve_paragraphs_block(
+    xml: &str,
+    (open, open_paragraph): (&Marker, &Range<usize>),
+    (close, close_paragraph): (&Marker, &Range<usize>),
+    keep: bool,
+) -> String {
+    let open_alone = is_alone(xml, open, open_paragraph);
+    let close_alone = is_alone(xml, close, close_paragraph);
+
+    let head = match (keep, open_alone) {
+        (true, true) => [
+            &xml[..open_paragraph.start],
+            &xml[open_paragraph.end..close_paragraph.start],
+        ]
+        .concat(),
+        (true, false) => [
+            &xml[..open.range.start],
+            &xml[open.range.end..close_paragraph.start],
+        ]
+        .concat(),
+        (false, true) => xml[..open_paragraph.start].to_string(),
+        (false, false) => [
+            &xml[..open.range.start],
+            &runs::strip_text(xml, open.range.start..open_paragraph.end),
+        ]
+        .concat(),
+    };
+    let tail = match (keep, close_alone) {
+        (_, true) => xml[close_paragraph.end..].to_string(),
+        (true, false) => [
+            &xml[close_paragraph.start..close.range.start],
+            &xml[close.range.end..],
+        ]
+        .concat(),
+        (false, false) => [
+            &runs::strip_text(xml, close_paragraph.start..close.range.end),
+            &xml[close.range.end..],
+        ]
+        .concat(),
+    };
+    head + &tail
+}
+
+/// Checks whether the block marker is the only visible text of its paragraph.
+fn is_alone(xml: &str, marker: &Marker, paragraph: &Range<usize>) -> bool {
+    runs::visible_text(&xml[paragraph.clone()]).trim() == &xml[marker.range.clone()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::docx_filler::Value;
+
+    fn paragraph(text: &str) -> String {
+        format!("<w:p><w:r><w:t>{}</w:t></w:r></w:p>", text)
+    }
+
+    fn resolve(xml: &str, values: &[(&str, &str)]) -> String {
+        let tokens: Vec<String> = values.iter().map(|(token, _)| token.to_string()).collect();
+        let values: Vec<Value> = values
+            .iter()
+            .map(|(_, value)| Value::from(*value))
+            .collect();
+        resolve_conditions(xml, &Delimiters::default(), &tokens, &values).unwrap()
+    }
+
+    #[test]
+    fn keeps_or_removes_inline_blocks() {
+        let xml = paragraph("Hi{{#if VIP}} dear{{/if}}{{#unless VIP}} you{{/unless}}!");
+        assert_eq!(resolve(&xml, &[("{{VIP}}", "yes")]), paragraph("Hi dear!"));
+        assert_eq!(resolve(&xml, &[("{{VIP}}", "no")]), paragraph("Hi you!"));
+    }
+
+    #[test]
+    fn removes_paragraphs_of_block_markers() {
+        let xml = [
+            paragraph("{{#if A}}"),
+            paragraph("text"),
+            paragraph("{{/if}}"),
+            paragraph("end"),
+        ]
+        .concat();
+        assert_eq!(
+            resolve(&xml, &[("{{A}}", "1")]),
+            [paragraph("text"), paragraph("end")].concat()
+        );
+        assert_eq!(resolve(&xml, &[("{{A}}", "")]), paragraph("end"));
+    }
+
+    #[test]
+    fn resolves_nested_blocks() {
+        let xml = paragraph("{{#if A}}a{{#unless B}}b{{#if C}}c{{/if}}{{/unless}}{{/if}}.");
+        let resolved = |a, b, c| resolve(&xml, &[("{{A}}", a), ("{{B}}", b), ("{{C}}", c)]);
+        assert_eq!(resolved("1", "", "1"), paragraph("abc."));
+        assert_eq!(resolved("1", "", "0"), paragraph("ab."));
+        assert_eq!(resolved("1", "1", "1"), paragraph("a."));
+        assert_eq!(resolved("", "", "1"), paragraph("."));
+    }
+
+    #[test]
+    fn keeps_blocks_of_unknown_tokens() {
+        let xml = paragraph("{{#if A}}a{{#if B}}b{{/if}}{{/if}}");
+        assert_eq!(
+            resolve(&xml, &[("{{B}}", "")]),
+            paragraph("{{#if A}}a{{/if}}")
+        );
+    }
+
+    #[test]
+    fn rejects_unbalanced_blocks() {
+        let delimiters = Delimiters::default();
+        assert!(validate_blocks(&paragraph("{{#if A}}{{#if B}}{{/if}}"), &delimiters).is_err());
+        assert!(validate_blocks(&paragraph("{{#if A}}{{/unless}}"), &delimiters).is_err());
+        assert!(validate_blocks(&paragraph("{{/if}}"), &delimiters).is_err());
+        assert!(validate_blocks(
+            &paragraph("{{#if A}}{{#unless B}}{{/unless}}{{/if}}"),
+            &delimiters
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn parses_condition_token() {
+        let delimiters = Delimiters::default();
+        assert_eq!(
+            condition_token("{{#unless  VIP }}", &delimiters),
+            Some("{{VIP}}".to_string())
+        );
+        assert!(is_marker("{{/if}}", &delimiters));
+        assert!(!is_marker("{{VIP}}", &delimiters));
+    }
+}
@@ -0,0 +1,220 @@
+use super::delimiters::Delimiters;
+use super::{formats, TokenPack, TokenPackArg, Value, ValuePack, ValuePackArg};
+use chrono::{Local, NaiveDateTime};
+use std::path::Path;
+
+/// Prefix of the built-in token name, e.g. `{{#index}}`.
+const BUILTIN_PREFIX: char = '#';
+/// Separator of the built-in token name and its argument, e.g. `{{#index:04}}`.
+const ARGUMENT_SEPARATOR: char = ':';
+
+/// Names of the built-in tokens, computed by the filler itself.
+const BUILTIN_NAMES: [&str; 4] = ["index", "today", "now", "template"];
+
+/// Format of the `{{#today}}` token value.
+const TODAY_FORMAT: &str = "%Y-%m-%d";
+/// Format of the `{{#now}}` token value.
+const NOW_FORMAT: &str = "%Y-%m-%d %H:%M";
+
+/// Built-in tokens used in the template (and output pattern), along with the details needed to compute their values:
+///
+/// * `{{#index}}` - sequential number of the generated document (`{{#index:04}}` padded with zeros to 4 digits)
+/// * `{{#today}}` - current date
+/// * `{{#now}}` - current date and time
+/// * `{{#template}}` - name of the template file (without extension)
+#[derive(Debug, Clone)]
+pub struct Builtins {
+    /// built-in tokens used, e.g. `{{#index:04}}`.
+    tokens: TokenPack,
+    /// name of the template file, without extension.
+    template: String,
+    /// time of the generation start, shared by all the generated documents.
+    now: NaiveDateTime,
+    /// delimiters of the tokens in the template.
+    delimiters: Delimiters,
+}
+
+impl Builtins {
+    /// Prepares the built-in tokens for generation of documents from the template.
+    ///
+    /// # Arguments
+    ///
+    /// * `tokens` - built-in tokens used in the template and output pattern
+    /// * `template_path` - path of the template file
+    /// * `delimiters` - delimiters of the tokens in the template
+    pub fn new(tokens: TokenPack, template_path: &Path, delimiters: &Delimiters) -> Builtins {
+        Builtins {
+            tokens,
+            template: template_path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            now: Local::now().naive_local(),
+            delimiters: delimiters.clone(),
+        }
+    }
+
+    /// Completes the tokens and values of a single generated document with the built-in tokens and their values.
+    ///
+    /// # Arguments
+    ///
+    /// * `tokens` - vector of tokens to be replaced
+    /// * `values` - vector of values to be filled in place of tokens
+    /// * `index` - sequential number of the generated document, starting from 1
+    pub fn complete(
+        &self,
+        tokens: TokenPackArg,
+        values: ValuePackArg,
+        index: usize,
+    ) -> (TokenPack, ValuePack) {
+        let builtin_values: ValuePack = self
+            .tokens
+            .iter()
+            .map(|token| Value::Text(self.value_of(token, index)))
+            .collect();
+        (
+            [tokens, &self.tokens[..]].concat(),
+            [values, &builtin_values[..]].concat(),
+        )
+    }
+
+    /// Computes the value of the built-in token for the generated document.
+    fn value_of(&self, token: &str, index: usize) -> String {
+        let (name, argument) = split_builtin(token, &self.delimiters);
+        match name {
+            "index" => match argument.and_then(|width| width.parse::<usize>().ok()) {
+                Some(width) => format!("{:0width$}", index, width = width),
+                None => index.to_string(),
+            },
+            "today" => self.now.format(TODAY_FORMAT).to_string(),
+            "now" => self.now.format(NOW_FORMAT).to_string(),
+            "template" => self.template.to_string(),
+            _ => String::new(),
+        }
+    }
+}
+
+/// Checks whether the (base) token is a built-in token, e.g. `{{#index}}` or `{{#index:04}}`.
+pub fn is_builtin(token: &str, delimiters: &Delimiters) -> bool {
+    delimiters.inner(token).starts_with(BUILTIN_PREFIX)
+        && BUILTIN_NAMES.contains(&split_builtin(token, delimiters).0)
+}
+
+/// Adds the built-in tokens found in the text (e.g. output pattern) to the already known ones.
+pub fn collect_builtins(text: &str, delimiters: &Delimiters, builtins: &mut TokenPack) {
+    for found in delimiters.token_re().find_iter(text) {
+        let token = formats::base_token(found.as_str(), delimiters);
+        if is_builtin(&token, delimiters) && !builtins.contains(&token) {
+            builtins.push(token);
+        }
+    }
+}
+
+/// Splits the built-in token into its name and optional argument (e.g. `index` and `04` for `{{#index:04}}`).
+fn split_builtin<'a>(token: &'a str, delimiters: &Delimiters) -> (&'a str, Option<&'a str>) {
+    let inner = delimiters
+        .inner(token)
+        .strip_prefix(BUILTIN_PREFIX)
+        .unwrap_or_default();
+    match inner.split_once(ARGUMENT_SEPARATOR) {
+        Some((name, argument)) => (name.trim(), Some(argument.trim())),
+        None => (inner.trim(), None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{validations, BatchOutput, LineFormat};
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn builtins(tokens: &[&str]) -> Builtins {
+        Builtins {
+            tokens: tokens.iter().map(|token| token.to_string()).collect(),
+            template: "offer".to_string(),
+            now: NaiveDate::from_ymd_opt(2022, 4, 1)
+                .unwrap()
+                .and_hms_opt(13, 5, 0)
+                .unwrap(),
+            delimiters: Delimiters::default(),
+        }
+    }
+
+    #[test]
+    fn recognizes_builtin_tokens() {
+        let delimiters = Delimiters::default();
+        assert!(is_builtin("{{#index}}", &delimiters));
+        assert!(is_builtin("{{#index:04}}", &delimiters));
+        assert!(is_builtin("{{#template}}", &delimiters));
+        assert!(!is_builtin("{{#unknown}}", &delimiters));
+        assert!(!is_builtin("{{index}}", &delimiters));
+        assert_eq!(
+            split_builtin("{{# index : 04 }}", &delimiters),
+            ("index", Some("04"))
+        );
+    }
+
+    #[test]
+    fn computes_builtin_values() {
+        let builtins = builtins(&[]);
+        assert_eq!(builtins.value_of("{{#index}}", 7), "7");
+        assert_eq!(builtins.value_of("{{#index:04}}", 7), "0007");
+        assert_eq!(builtins.value_of("{{#index:2}}", 123), "123");
+        assert_eq!(builtins.value_of("{{#today}}", 1), "2022-04-01");
+        assert_eq!(builtins.value_of("{{#now}}", 1), "2022-04-01 13:05");
+        assert_eq!(builtins.value_of("{{#template}}", 1), "offer");
+    }
+
+    #[test]
+    fn takes_template_name_from_path() {
+        let builtins = Builtins::new(
+            vec!["{{#template}}".to_string()],
+            Path::new("/templates/offer.v2.docx"),
+            &Delimiters::default(),
+        );
+        assert_eq!(builtins.value_of("{{#template}}", 1), "offer.v2");
+    }
+
+    #[test]
+    fn collects_builtins_of_output_pattern() {
+        let mut tokens = vec!["{{#today}}".to_string()];
+        collect_builtins(
+            "{{#index:04}}-{{NAME|upper}}-{{#today}}-{{#index:04}}.docx",
+            &Delimiters::default(),
+            &mut tokens,
+        );
+        assert_eq!(tokens, vec!["{{#today}}", "{{#index:04}}"]);
+    }
+
+    #[test]
+    fn completes_tokens_and_values() {
+        let builtins = builtins(&["{{#index:03}}", "{{#template}}"]);
+        let (tokens, values) =
+            builtins.complete(&["{{NAME}}".to_string()], &[Value::from("Ann")], 2);
+        assert_eq!(tokens, vec!["{{NAME}}", "{{#index:03}}", "{{#template}}"]);
+        let values: Vec<String> = values.iter().map(Value::text).collect();
+        assert_eq!(values, vec!["Ann", "002", "offer"]);
+    }
+
+    #[test]
+    fn does_not_require_values_of_builtins() {
+        let format = LineFormat {
+            separator: ";".to_string(),
+            lists: vec![false],
+        };
+        let validate = |pattern: &str| {
+            validations::validate_batch(
+                &["{{NAME}}".to_string()],
+                &[None],
+                &[None],
+                &builtins(&["{{#index}}"]),
+                "Ann\nAnn",
+                &format,
+                pattern,
+                BatchOutput::Separate,
+            )
+        };
+        assert!(validate("{{NAME}}-{{#index}}.docx").is_ok());
+        assert!(validate("{{NAME}}.docx").is_err());
+    }
+}
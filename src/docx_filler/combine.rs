@@ -0,0 +1,651 @@
+use super::{images, xml};
+use regex::{Captures, Regex};
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+
+/// Name of the main document part.
+const DOCUMENT_PART: &str = "word/document.xml";
+/// Name of the numbering definitions part.
+pub const NUMBERING_PART: &str = "word/numbering.xml";
+/// Number of the list levels, that can be restarted in each of the combined documents.
+const LIST_LEVELS: usize = 9;
+
+/// Elements whose ids have to be unique in the document - drawings, and bookmarks (their start and end
+/// share the id).
+const UNIQUE_IDS: [&[&str]; 2] = [&["wp:docPr"], &["w:bookmarkStart", "w:bookmarkEnd"]];
+
+/// Parts with notes (footnotes, endnotes and comments), along with the note element,
+/// and the elements referring to the notes from the document.
+const NOTE_PARTS: [(&str, &str, &[&str]); 3] = [
+    ("word/footnotes.xml", "w:footnote", &["w:footnoteReference"]),
+    ("word/endnotes.xml", "w:endnote", &["w:endnoteReference"]),
+    (
+        "word/comments.xml",
+        "w:comment",
+        &[
+            "w:commentRangeStart",
+            "w:commentRangeEnd",
+            "w:commentReference",
+        ],
+    ),
+];
+
+lazy_static! {
+    /// Matches a single relationship of the relationships part.
+    static ref RELATIONSHIP_RE: Regex = Regex::new(r"<Relationship\s[^>]*?/>").unwrap();
+    /// Matches the id of a relationship.
+    static ref ID_RE: Regex = Regex::new(r#"\bId="([^"]*)""#).unwrap();
+    /// Matches the type of a relationship.
+    static ref TYPE_RE: Regex = Regex::new(r#"\bType="([^"]*)""#).unwrap();
+    /// Matches the target of a relationship.
+    static ref TARGET_RE: Regex = Regex::new(r#"\bTarget="([^"]*)""#).unwrap();
+    /// Matches the reference to a relationship from the document, e.g. `r:id="rId5"` or `r:embed="rId7"`.
+    static ref REFERENCE_RE: Regex = Regex::new(r#"\b(r:\w+=")([^"]*)""#).unwrap();
+    /// Matches the numbering (list) used by a paragraph.
+    static ref NUM_ID_RE: Regex = Regex::new(r#"(<w:numId\s[^>]*?\bw:val=")(\d+)""#).unwrap();
+    /// Matches the numbering definition id.
+    static ref NUM_RE: Regex = Regex::new(r#"<w:num\s[^>]*?\bw:numId="(\d+)""#).unwrap();
+    /// Matches the list level overridden by the numbering definition.
+    static ref LEVEL_OVERRIDE_RE: Regex = Regex::new(r#"<w:lvlOverride\s[^>]*?\bw:ilvl="(\d+)""#).unwrap();
+    /// Matches the id of an element (e.g. `<w:bookmarkStart w:id="0" .../>` or `<wp:docPr id="1" .../>`),
+    /// along with the tag name of the element.
+    static ref ELEMENT_ID_RE: Regex =
+        Regex::new(r#"(<([\w:]+)\s(?:[^>]*?\s)?(?:w:)?id=")(-?\d+)""#).unwrap();
+    /// Matches the type of the section start (e.g. continuous), in the section properties.
+    static ref SECTION_TYPE_RE: Regex = Regex::new(r"<w:type\s[^>]*/>").unwrap();
+    /// Matches the page numbering of the section, in the section properties.
+    static ref PAGE_NUMBERING_RE: Regex = Regex::new(r"<w:pgNumType\b[^>]*?/?>").unwrap();
+    /// Matches the starting page number, in the page numbering of the section.
+    static ref PAGE_START_RE: Regex = Regex::new(r#"\sw:start="[^"]*""#).unwrap();
+    /// Matches the section properties following the page numbering (or the end of the section properties).
+    static ref AFTER_PAGE_NUMBERING_RE: Regex = Regex::new(
+        r"<w:(?:cols|formProt|vAlign|noEndnote|titlePg|textDirection|bidi|rtlGutter|docGrid|printerSettings|sectPrChange)\b|</w:sectPr>"
+    ).unwrap();
+}
+
+/// Single document combined of the documents generated for each line of the batch.
+/// Each of the documents starts a new section on a new page, with its own page numbering.
+/// Relationships (hyperlinks, images, headers and footers), numbering of the lists, footnotes, endnotes
+/// and comments of the documents are merged, parts shared by all the documents (e.g. styles) are kept as they are.
+/// Contents of the next documents are collected, and put into the parts once, when the combined document is complete.
+#[derive(Debug, Default)]
+pub struct CombinedDocument {
+    /// contents of the package parts of the combined document (the first document, with the rest merged into it).
+    parts: Vec<(String, String)>,
+    /// number of the documents combined so far.
+    count: usize,
+    /// body contents of each of the combined documents, joined in the main document when it is complete.
+    bodies: Vec<String>,
+    /// contents added to the end of the parts (relationships, notes, ...), by the part name.
+    appended: HashMap<String, String>,
+    /// the highest ids used by the combined documents, by the kind of the id (the element, or the notes part).
+    last_ids: HashMap<String, i64>,
+    /// relationships of the main document of the combined document.
+    relationships: HashSet<String>,
+    /// ids of the relationships of the main document of the combined document.
+    relationship_ids: HashSet<String>,
+    /// numbering definitions of the template, along with their ids.
+    nums: Vec<(usize, String)>,
+    /// the highest id of the numbering definitions of the combined document.
+    last_num: usize,
+}
+
+impl CombinedDocument {
+    /// Creates the combined document without any documents, along with the template parts that get modified
+    /// when the documents are merged.
+    ///
+    /// # Arguments
+    ///
+    /// * `content_types` - contents of the content types part of the template
+    /// * `numbering` - contents of the numbering definitions part of the template, if there is any
+    pub fn new(content_types: (&str, String), numbering: Option<String>) -> CombinedDocument {
+        let mut parts = vec![(content_types.0.to_string(), content_types.1)];
+        let mut nums: Vec<(usize, String)> = Default::default();
+        if let Some(numbering) = numbering {
+            nums = xml::element_ranges(&numbering, "w:num")
+                .into_iter()
+                .filter_map(|range| {
+                    let num = &numbering[range];
+                    let id = NUM_RE.captures(num)?[1].parse().ok()?;
+                    Some((id, num.to_string()))
+                })
+                .collect();
+            parts.push((NUMBERING_PART.to_string(), numbering));
+        }
+        let last_num = nums.iter().map(|(id, _)| *id).max().unwrap_or(0);
+        CombinedDocument {
+            parts,
+            nums,
+            last_num,
+            ..Default::default()
+        }
+    }
+
+    /// Returns the contents of the package parts of the complete combined document.
+    pub fn into_parts(mut self) -> Vec<(String, String)> {
+        let document = self
+            .parts
+            .iter_mut()
+            .find(|(part, _)| part == DOCUMENT_PART);
+        if let Some((_, document)) = document {
+            if let Some(body) = body_range(document).filter(|_| !self.bodies.is_empty()) {
+                document.replace_range(body, &self.bodies.concat());
+            }
+        }
+        for (name, added) in self.appended.iter() {
+            if let Some((_, contents)) = self.parts.iter_mut().find(|(part, _)| part == name) {
+                if let Some(end) = end_of_contents(contents) {
+                    contents.insert_str(end, added);
+                }
+            }
+        }
+        self.parts
+    }
+
+    /// Appends the generated document to the combined one, starting on a new page.
+    ///
+    /// # Arguments
+    ///
+    /// * `parts` - filled text parts of the generated document (main document, headers, relationships, ...)
+    pub fn append(&mut self, parts: Vec<(String, String)>) {
+        self.count += 1;
+        if self.count == 1 {
+            self.parts.extend(parts);
+            self.start();
+            return;
+        }
+
+        let mut parts: HashMap<String, String> = parts.into_iter().collect();
+        let document = match parts.remove(DOCUMENT_PART) {
+            Some(document) => document,
+            None => return,
+        };
+        let relationships = parts
+            .get(&images::rels_part_of(DOCUMENT_PART))
+            .cloned()
+            .unwrap_or_default();
+        let renamed = self.merge_relationships(&relationships, &parts);
+        let document = REFERENCE_RE.replace_all(&document, |cap: &Captures| {
+            let id = renamed.get(&cap[2]).map_or(&cap[2], String::as_str);
+            format!(r#"{}{}""#, &cap[1], id)
+        });
+        let document = self.merge_notes(&document, &parts);
+        let document = self.merge_numbering(&document);
+        let document = self.renumber_ids(&document);
+        self.merge_body(&document);
+    }
+
+    /// Remembers the body, the highest ids and the relationships of the first document,
+    /// the next documents are merged into.
+    fn start(&mut self) {
+        let document = self.part(DOCUMENT_PART).unwrap_or_default();
+        let body = body_range(document).map(|body| document[body].to_string());
+        let mut last_ids: HashMap<String, i64> = UNIQUE_IDS
+            .iter()
+            .filter_map(|elements| Some((elements[0].to_string(), max_id(document, elements)?)))
+            .collect();
+        for (part, element, _) in NOTE_PARTS.iter() {
+            if let Some(id) = self.part(part).and_then(|notes| max_id(notes, &[element])) {
+                last_ids.insert(part.to_string(), id);
+            }
+        }
+        let relationships: Vec<String> = RELATIONSHIP_RE
+            .find_iter(
+                self.part(&images::rels_part_of(DOCUMENT_PART))
+                    .unwrap_or_default(),
+            )
+            .map(|found| found.as_str().to_string())
+            .collect();
+
+        self.bodies.extend(body);
+        self.last_ids = last_ids;
+        self.relationship_ids = relationships
+            .iter()
+            .map(|relationship| attribute(&ID_RE, relationship).to_string())
+            .collect();
+        self.relationships = relationships.into_iter().collect();
+    }
+
+    /// Returns the contents of the part of the combined document.
+    fn part(&self, name: &str) -> Option<&str> {
+        self.parts
+            .iter()
+            .find(|(part, _)| part == name)
+            .map(|(_, contents)| contents.as_str())
+    }
+
+    /// Sets the contents of the part of the combined document, adding the part if it is missing.
+    fn set_part(&mut self, name: &str, contents: String) {
+        match self.parts.iter_mut().find(|(part, _)| part == name) {
+            Some((_, existing)) => *existing = contents,
+            None => self.parts.push((name.to_string(), contents)),
+        }
+    }
+
+    /// Adds the contents to the end of the part of the combined document, when the document is complete.
+    fn append_to(&mut self, name: &str, contents: &str) {
+        if !contents.is_empty() {
+            self.appended
+                .entry(name.to_string())
+                .or_default()
+                .push_str(contents);
+        }
+    }
+
+    /// Merges the relationships of the next document into the relationships of the main document.
+    /// Relationships the same as the ones of the combined document are shared, the rest is added
+    /// (with a new id, if the id is taken). Headers and footers differing from the ones of the combined document
+    /// are added as new parts.
+    ///
+    /// Returns the new ids of the renamed relationships, by their original ids.
+    fn merge_relationships(
+        &mut self,
+        relationships: &str,
+        parts: &HashMap<String, String>,
+    ) -> HashMap<String, String> {
+        let mut renamed: HashMap<String, String> = Default::default();
+        let mut appended = String::new();
+        for found in RELATIONSHIP_RE.find_iter(relationships) {
+            let relationship = found.as_str();
+            let id = attribute(&ID_RE, relationship);
+            let kind = attribute(&TYPE_RE, relationship);
+            let target = attribute(&TARGET_RE, relationship);
+
+            let mut added = relationship.to_string();
+            if kind.ends_with("/header") || kind.ends_with("/footer") {
+                let part = format!("word/{}", target.trim_start_matches('/'));
+                match parts.get(&part) {
+                    Some(contents) if self.part(&part) != Some(contents) => {
+                        let copy = self.copy_part(&part, contents, parts);
+                        let copy_target = copy.trim_start_matches("word/");
+                        added = added.replacen(
+                            &format!(r#"Target="{}""#, target),
+                            &format!(r#"Target="{}""#, copy_target),
+                            1,
+                        );
+                    }
+                    _ => {}
+                }
+            }
+
+            if self.relationships.contains(&added) {
+                continue;
+            }
+            if self.relationship_ids.contains(id) {
+                let new_id = format!("{}_{}", id, self.count);
+                added = added.replacen(
+                    &format!(r#"Id="{}""#, id),
+                    &format!(r#"Id="{}""#, new_id),
+                    1,
+                );
+                renamed.insert(id.to_string(), new_id);
+            }
+            self.relationship_ids
+                .insert(attribute(&ID_RE, &added).to_string());
+            appended.push_str(&added);
+            self.relationships.insert(added);
+        }
+        if self.part(&images::rels_part_of(DOCUMENT_PART)).is_some() {
+            self.append_to(&images::rels_part_of(DOCUMENT_PART), &appended);
+        }
+        renamed
+    }
+
+    /// Adds the copy of the header or footer part of the next document (along with its relationships),
+    /// declaring its content type. Returns the name of the new part, e.g. `word/header1_2.xml`.
+    fn copy_part(&mut self, part: &str, contents: &str, parts: &HashMap<String, String>) -> String {
+        let (stem, extension) = part.rsplit_once('.').unwrap_or((part, "xml"));
+        let copy = format!("{}_{}.{}", stem, self.count, extension);
+        self.set_part(&copy, contents.to_string());
+        if let Some(relationships) = parts.get(&images::rels_part_of(part)) {
+            self.set_part(&images::rels_part_of(&copy), relationships.to_string());
+        }
+
+        let (content_types_name, content_types) = &self.parts[0];
+        let part_name = format!(r#"PartName="/{}""#, part);
+        let declaration = content_types.find(&part_name).and_then(|pos| {
+            let start = content_types[..pos].rfind("<Override")?;
+            let end = pos + content_types[pos..].find("/>")? + 2;
+            let declaration = &content_types[start..end];
+            Some(declaration.replacen(&format!("/{}", part), &format!("/{}", copy), 1))
+        });
+        if let Some(declaration) = declaration {
+            let content_types_name = content_types_name.clone();
+            self.append_to(&content_types_name, &declaration);
+        }
+        copy
+    }
+
+    /// Appends the notes (footnotes, endnotes and comments) of the next document to the notes
+    /// of the combined document, with new ids. Returns the document referring to the new ids.
+    fn merge_notes(&mut self, document: &str, parts: &HashMap<String, String>) -> String {
+        let mut document = document.to_string();
+        for (part, element, references) in NOTE_PARTS.iter() {
+            let notes = match (self.part(part), parts.get(*part)) {
+                (Some(_), Some(notes)) => notes,
+                _ => continue,
+            };
+            let offset = self.last_ids.get(*part).map_or(0, |last| last + 1);
+            let added: String = xml::element_ranges(notes, element)
+                .into_iter()
+                .map(|range| &notes[range])
+                .filter(|note| !note[..note.find('>').unwrap_or(0)].contains("w:type="))
+                .map(|note| shift_ids(note, &[element], offset))
+                .collect();
+            if let Some(id) = max_id(&added, &[element]) {
+                self.last_ids.insert(part.to_string(), id);
+            }
+            self.append_to(part, &added);
+            document = shift_ids(&document, references, offset);
+        }
+        document
+    }
+
+    /// Adds new numbering definitions for the lists of the next document, restarting their numbering.
+    /// Returns the document using the new definitions.
+    fn merge_numbering(&mut self, document: &str) -> String {
+        let mut renumbered: HashMap<usize, usize> = Default::default();
+        let mut added = String::new();
+        for cap in NUM_ID_RE.captures_iter(document) {
+            let id: usize = cap[2].parse().unwrap_or(0);
+            if id == 0 || renumbered.contains_key(&id) {
+                continue;
+            }
+            let num = match self.nums.iter().find(|(num_id, _)| *num_id == id) {
+                Some((_, num)) => num,
+                None => continue,
+            };
+            self.last_num += 1;
+            renumbered.insert(id, self.last_num);
+            added.push_str(&restarted_num(num, id, self.last_num));
+        }
+        if added.is_empty() {
+            return document.to_string();
+        }
+
+        self.append_to(NUMBERING_PART, &added);
+        NUM_ID_RE
+            .replace_all(document, |cap: &Captures| {
+                let id: usize = cap[2].parse().unwrap_or(0);
+                let id = renumbered.get(&id).copied().unwrap_or(id);
+                format!(r#"{}{}""#, &cap[1], id)
+            })
+            .to_string()
+    }
+
+    /// Gives the elements of the next document, whose ids have to be unique in the document
+    /// (drawings and bookmarks), ids following the ones of the combined document.
+    fn renumber_ids(&mut self, document: &str) -> String {
+        let mut document = document.to_string();
+        for elements in UNIQUE_IDS.iter() {
+            let kind = elements[0].to_string();
+            let offset = self.last_ids.get(&kind).map_or(0, |last| last + 1);
+            document = shift_ids(&document, elements, offset);
+            if let Some(id) = max_id(&document, elements) {
+                self.last_ids.insert(kind, id);
+            }
+        }
+        document
+    }
+
+    /// Appends the body of the next document to the body of the combined document.
+    /// Last section of the combined document is closed by a section break, and the first section
+    /// of the next document starts on a new page with page number 1.
+    fn merge_body(&mut self, document: &str) {
+        let (body, next_body) = match (self.bodies.last_mut(), body_range(document)) {
+            (Some(body), Some(next_body)) => (body, next_body),
+            _ => return,
+        };
+
+        match final_section(body) {
+            Some(section) => {
+                let properties = body[section.clone()].to_string();
+                body.replace_range(
+                    section,
+                    &format!("<w:p><w:pPr>{}</w:pPr></w:p>", properties),
+                );
+            }
+            None => body.push_str(r#"<w:p><w:r><w:br w:type="page"/></w:r></w:p>"#),
+        }
+
+        let mut next = document[next_body].to_string();
+        if let Some(section) = xml::element_ranges(&next, "w:sectPr").into_iter().next() {
+            let properties = restarted_section(&next[section.clone()]);
+            next.replace_range(section, &properties);
+        }
+        self.bodies.push(next);
+    }
+}
+
+/// Shifts the ids of the elements with the specified tag names by the offset.
+fn shift_ids(xml: &str, elements: &[&str], offset: i64) -> String {
+    ELEMENT_ID_RE
+        .replace_all(xml, |cap: &Captures| match elements.contains(&&cap[2]) {
+            true => {
+                let id = cap[3].parse::<i64>().unwrap_or(0);
+                format!(r#"{}{}""#, &cap[1], id + offset)
+            }
+            false => cap[0].to_string(),
+        })
+        .to_string()
+}
+
+/// Returns the highest id of the elements with the specified tag names, if there are any.
+fn max_id(xml: &str, elements: &[&str]) -> Option<i64> {
+    ELEMENT_ID_RE
+        .captures_iter(xml)
+        .filter(|cap| elements.contains(&&cap[2]))
+        .filter_map(|cap| cap[3].parse().ok())
+        .max()
+}
+
+/// Returns the byte offset of the part, where the contents are added to its end - before the closing tag
+/// of its root element (or before the cleanup elements following the numbering definitions).
+fn end_of_contents(part: &str) -> Option<usize> {
+    part.find("<w:numIdMacAtCleanup")
+        .or_else(|| part.rfind("</"))
+}
+
+/// Returns the value of the attribute matched by the regex, or an empty string if there is no such attribute.
+fn attribute<'a>(re: &Regex, element: &'a str) -> &'a str {
+    re.captures(element)
+        .and_then(|cap| cap.get(1))
+        .map_or("", |value| value.as_str())
+}
+
+/// Returns the byte range of the body contents of the main document (between the body tags).
+fn body_range(document: &str) -> Option<Range<usize>> {
+    let body = xml::element_ranges(document, "w:body").into_iter().next()?;
+    let start = body.start + document[body.clone()].find('>')? + 1;
+    let end = body.start + document[body].rfind("</w:body>")?;
+    Some(start..end)
+}
+
+/// Returns the byte range of the section properties of the last section in the body contents, if there are any.
+fn final_section(body: &str) -> Option<Range<usize>> {
+    // the outermost one, not the previous section properties nested in its tracked change
+    xml::element_ranges(body, "w:sectPr")
+        .into_iter()
+        .find(|section| body[section.end..].trim().is_empty())
+}
+
+/// Makes the section start on a new page with the page number 1.
+fn restarted_section(properties: &str) -> String {
+    let properties = match properties.strip_suffix("/>") {
+        Some(start) => format!("{}></w:sectPr>", start),
+        None => properties.to_string(),
+    };
+    let properties = SECTION_TYPE_RE.replace_all(&properties, "");
+    match PAGE_NUMBERING_RE.find(&properties) {
+        Some(found) => {
+            let numbering = PAGE_START_RE.replace(found.as_str(), "");
+            let numbering = numbering.replacen("<w:pgNumType", r#"<w:pgNumType w:start="1""#, 1);
+            [
+                &properties[..found.start()],
+                &numbering,
+                &properties[found.end()..],
+            ]
+            .concat()
+        }
+        None => match AFTER_PAGE_NUMBERING_RE.find(&properties) {
+            Some(found) => [
+                &properties[..found.start()],
+                r#"<w:pgNumType w:start="1"/>"#,
+                &properties[found.start()..],
+            ]
+            .concat(),
+            None => properties.to_string(),
+        },
+    }
+}
+
+/// Creates a copy of the numbering definition with a new id, restarting the numbering of all its list levels.
+fn restarted_num(num: &str, id: usize, new_id: usize) -> String {
+    let overridden: Vec<usize> = LEVEL_OVERRIDE_RE
+        .captures_iter(num)
+        .filter_map(|cap| cap[1].parse().ok())
+        .collect();
+    let overrides: String = (0..LIST_LEVELS)
+        .filter(|level| !overridden.contains(level))
+        .map(|level| {
+            format!(
+                r#"<w:lvlOverride w:ilvl="{}"><w:startOverride w:val="1"/></w:lvlOverride>"#,
+                level
+            )
+        })
+        .collect();
+    let num = num.replacen(
+        &format!(r#"w:numId="{}""#, id),
+        &format!(r#"w:numId="{}""#, new_id),
+        1,
+    );
+    match num.rfind("</w:num>") {
+        Some(end) => [&num[..end], &overrides, &num[end..]].concat(),
+        None => num,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn document(body: &str) -> (String, String) {
+        let xml = format!("<w:document><w:body>{}</w:body></w:document>", body);
+        (DOCUMENT_PART.to_string(), xml)
+    }
+
+    fn combine(bodies: &[&str]) -> String {
+        let mut combined =
+            CombinedDocument::new(("[Content_Types].xml", "<Types></Types>".to_string()), None);
+        for body in bodies {
+            combined.append(vec![document(body)]);
+        }
+        let parts = combined.into_parts();
+        let document = parts
+            .iter()
+            .find(|(name, _)| name == DOCUMENT_PART)
+            .unwrap();
+        let body = body_range(&document.1).unwrap();
+        document.1[body].to_string()
+    }
+
+    #[test]
+    fn closes_sections_of_combined_documents() {
+        let section =
+            r#"<w:sectPr><w:type w:val="continuous"/><w:pgNumType w:start="5"/></w:sectPr>"#;
+        let body = format!("<w:p>A</w:p>{}", section);
+        assert_eq!(
+            combine(&[&body, &body]),
+            [
+                "<w:p>A</w:p><w:p><w:pPr>",
+                section,
+                r#"</w:pPr></w:p><w:p>A</w:p><w:sectPr><w:pgNumType w:start="1"/></w:sectPr>"#,
+            ]
+            .concat()
+        );
+    }
+
+    #[test]
+    fn breaks_page_between_documents_without_sections() {
+        assert_eq!(
+            combine(&["<w:p>A</w:p>", "<w:p>B</w:p>"]),
+            r#"<w:p>A</w:p><w:p><w:r><w:br w:type="page"/></w:r></w:p><w:p>B</w:p>"#
+        );
+    }
+
+    #[test]
+    fn finds_final_section() {
+        let body =
+            r#"<w:p/><w:sectPr><w:sectPrChange w:id="1"><w:sectPr/></w:sectPrChange></w:sectPr> "#;
+        let section = final_section(body).unwrap();
+        assert!(body[section].starts_with("<w:sectPr><w:sectPrChange"));
+        assert_eq!(final_section("<w:sectPr/><w:p/>"), None);
+    }
+
+    #[test]
+    fn restarts_page_numbering() {
+        assert_eq!(
+            restarted_section("<w:sectPr/>"),
+            r#"<w:sectPr><w:pgNumType w:start="1"/></w:sectPr>"#
+        );
+        assert_eq!(
+            restarted_section(
+                r#"<w:sectPr><w:pgNumType w:fmt="decimal" w:start="3"/><w:cols/></w:sectPr>"#
+            ),
+            r#"<w:sectPr><w:pgNumType w:start="1" w:fmt="decimal"/><w:cols/></w:sectPr>"#
+        );
+        assert_eq!(
+            restarted_section("<w:sectPr><w:pgSz/><w:cols/></w:sectPr>"),
+            r#"<w:sectPr><w:pgSz/><w:pgNumType w:start="1"/><w:cols/></w:sectPr>"#
+        );
+    }
+
+    #[test]
+    fn renumbers_drawings_and_bookmarks() {
+        let body = r#"<w:bookmarkStart w:id="0" w:name="a"/><w:bookmarkEnd w:id="0"/><wp:docPr id="1" name="x"/><w:p/>"#;
+        assert_eq!(
+            combine(&[body, body]),
+            [
+                body,
+                r#"<w:p><w:r><w:br w:type="page"/></w:r></w:p>"#,
+                r#"<w:bookmarkStart w:id="1" w:name="a"/><w:bookmarkEnd w:id="1"/><wp:docPr id="3" name="x"/><w:p/>"#,
+            ]
+            .concat()
+        );
+    }
+
+    #[test]
+    fn merges_notes_with_new_ids() {
+        let footnotes = r#"<w:footnotes><w:footnote w:type="separator" w:id="0"/><w:footnote w:id="1">N</w:footnote></w:footnotes>"#;
+        let comments = r#"<w:comments><w:comment w:id="0">C</w:comment></w:comments>"#;
+        let body = r#"<w:p><w:commentRangeStart w:id="0"/><w:r><w:footnoteReference w:id="1"/></w:r><w:commentRangeEnd w:id="0"/><w:r><w:commentReference w:id="0"/></w:r></w:p>"#;
+        let mut combined =
+            CombinedDocument::new(("[Content_Types].xml", "<Types></Types>".to_string()), None);
+        for _ in 0..3 {
+            combined.append(vec![
+                document(body),
+                ("word/footnotes.xml".to_string(), footnotes.to_string()),
+                ("word/comments.xml".to_string(), comments.to_string()),
+            ]);
+        }
+        let parts = combined.into_parts();
+        let part = |name: &str| &parts.iter().find(|(part, _)| part == name).unwrap().1;
+        assert_eq!(
+            part("word/footnotes.xml"),
+            r#"<w:footnotes><w:footnote w:type="separator" w:id="0"/><w:footnote w:id="1">N</w:footnote><w:footnote w:id="3">N</w:footnote><w:footnote w:id="5">N</w:footnote></w:footnotes>"#
+        );
+        assert_eq!(
+            part("word/comments.xml"),
+            r#"<w:comments><w:comment w:id="0">C</w:comment><w:comment w:id="1">C</w:comment><w:comment w:id="2">C</w:comment></w:comments>"#
+        );
+        let document = part(DOCUMENT_PART);
+        for id in 0..3 {
+            for reference in ["commentRangeStart", "commentRangeEnd", "commentReference"] {
+                assert!(document.contains(&format!(r#"<w:{} w:id="{}"/>"#, reference, id)));
+            }
+        }
+        for id in [1, 3, 5] {
+            assert!(document.contains(&format!(r#"<w:footnoteReference w:id="{}"/>"#, id)));
+        }
+    }
+}
@@ -0,0 +1,204 @@
+use super::delimiters::Delimiters;
+use super::xml;
+use regex::Regex;
+use std::ops::Range;
+
+lazy_static! {
+    /// Matches tag of the content control, e.g. `<w:tag w:val="CLIENT"/>`.
+    static ref TAG_RE: Regex = Regex::new(r#"<w:tag\s[^>]*?\bw:val="([^"]*)""#).unwrap();
+    /// Matches alias (title) of the content control, e.g. `<w:alias w:val="Client"/>`.
+    static ref ALIAS_RE: Regex = Regex::new(r#"<w:alias\s[^>]*?\bw:val="([^"]*)""#).unwrap();
+    /// Matches the mark of the content control showing its placeholder text.
+    static ref PLACEHOLDER_RE: Regex = Regex::new(r"<w:showingPlcHdr(?:\s[^>]*)?/>").unwrap();
+    /// Matches properties of the content controls that do not hold a plain text (pictures, check boxes,
+    /// dates, ...), or that hold table cells or rows.
+    static ref NON_TEXT_RE: Regex =
+        Regex::new(r"<(?:w:picture|w14:checkbox|w:date|w:docPartObj|w:group)\b|<w:t[cr][\s>]").unwrap();
+}
+
+/// Turns the content controls of the document part (`<w:sdt>` elements) identified by tag or alias into tokens
+/// (e.g. `{{CLIENT}}` for the control tagged `CLIENT`), so that they get filled the same way as the text tokens.
+/// Contents of each control is replaced by the token, keeping formatting of its first paragraph and run.
+/// Controls without tag and alias, or not holding a plain text (pictures, check boxes, dates, ...) are kept
+/// as they are.
+///
+/// # Arguments
+///
+/// * `xml` - contents of the document part
+/// * `delimiters` - delimiters of the tokens in the template
+/// * `remove` - whether the controls are to be removed, keeping just their contents (the token)
+pub fn controls_to_tokens(xml: &str, delimiters: &Delimiters, remove: bool) -> String {
+    let controls = xml::element_ranges(xml, "w:sdt");
+    let innermost: Vec<&Range<usize>> = controls
+        .iter()
+        .filter(|outer| {
+            !controls.iter().any(|inner| {
+                inner != *outer && outer.start <= inner.start && inner.end <= outer.end
+            })
+        })
+        .collect();
+
+    let mut output = xml.to_string();
+    for control in innermost.into_iter().rev() {
+        if let Some(replaced) = control_to_token(&xml[control.clone()], delimiters, remove) {
+            output.replace_range(control.clone(), &replaced);
+        }
+    }
+    output
+}
+
+/// Turns a single content control into the token, if it is identified by tag or alias and holds a text.
+fn control_to_token(control: &str, delimiters: &Delimiters, remove: bool) -> Option<String> {
+    let properties = xml::element_ranges(control, "w:sdtPr").into_iter().next()?;
+    let content = xml::element_ranges(control, "w:sdtContent")
+        .into_iter()
+        .next()?;
+    let properties_xml = &control[properties.clone()];
+    if NON_TEXT_RE.is_match(properties_xml) || NON_TEXT_RE.is_match(&control[content.clone()]) {
+        return None;
+    }
+    let name = TAG_RE
+        .captures(properties_xml)
+        .or_else(|| ALIAS_RE.captures(properties_xml))
+        .and_then(|cap| cap.get(1))
+        .map(|name| xml::unescape(name.as_str()))
+        .filter(|name| !name.trim().is_empty())?;
+
+    let content_xml = &control[content.clone()];
+    let run_properties = xml::element_ranges(content_xml, "w:r")
+        .into_iter()
+        .next()
+        .and_then(|run| {
+            let run_xml = &content_xml[run];
+            let rpr = xml::element_ranges(run_xml, "w:rPr").into_iter().next()?;
+            Some(run_xml[rpr].to_string())
+        })
+        .unwrap_or_default();
+    let token = xml::escape(&delimiters.wrap(&name), xml::XmlContext::RunText);
+    let run = format!("<w:r>{}<w:t>{}</w:t></w:r>", run_properties, token);
+    let filled = if xml::element_ranges(content_xml, "w:p").is_empty() {
+        run
+    } else {
+        let paragraph_properties = xml::element_ranges(content_xml, "w:pPr")
+            .into_iter()
+            .next()
+            .map(|ppr| content_xml[ppr].to_string())
+            .unwrap_or_default();
+        format!("<w:p>{}{}</w:p>", paragraph_properties, run)
+    };
+
+    if remove {
+        return Some(filled);
+    }
+    let properties_xml = PLACEHOLDER_RE.replace_all(properties_xml, "");
+    Some(
+        [
+            &control[..properties.start],
+            &properties_xml,
+            &control[properties.end..content.start],
+            "<w:sdtContent>",
+            &filled,
+            "</w:sdtContent>",
+            &control[content.end..],
+        ]
+        .concat(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn control(properties: &str, content: &str) -> String {
+        format!(
+            "<w:sdt><w:sdtPr>{}</w:sdtPr><w:sdtContent>{}</w:sdtContent></w:sdt>",
+            properties, content
+        )
+    }
+
+    fn to_tokens(xml: &str, remove: bool) -> String {
+        controls_to_tokens(xml, &Delimiters::default(), remove)
+    }
+
+    #[test]
+    fn turns_control_into_token_by_tag() {
+        let content =
+            "<w:r><w:rPr><w:b/></w:rPr><w:t>Client</w:t></w:r><w:r><w:t> name</w:t></w:r>";
+        let xml = control(
+            r#"<w:alias w:val="Client name"/><w:tag w:val="CLIENT"/><w:showingPlcHdr/>"#,
+            content,
+        );
+        assert_eq!(
+            to_tokens(&xml, false),
+            control(
+                r#"<w:alias w:val="Client name"/><w:tag w:val="CLIENT"/>"#,
+                "<w:r><w:rPr><w:b/></w:rPr><w:t>{{CLIENT}}</w:t></w:r>"
+            )
+        );
+    }
+
+    #[test]
+    fn turns_control_into_token_by_alias() {
+        let content = concat!(
+            r#"<w:p><w:pPr><w:jc w:val="center"/></w:pPr><w:r><w:rPr><w:i/></w:rPr><w:t>A</w:t></w:r></w:p>"#,
+            "<w:p><w:r><w:t>B</w:t></w:r></w:p>"
+        );
+        let xml = control(r#"<w:alias w:val="Client &amp; Co"/>"#, content);
+        assert_eq!(
+            to_tokens(&xml, false),
+            control(
+                r#"<w:alias w:val="Client &amp; Co"/>"#,
+                r#"<w:p><w:pPr><w:jc w:val="center"/></w:pPr><w:r><w:rPr><w:i/></w:rPr><w:t>{{Client &amp; Co}}</w:t></w:r></w:p>"#
+            )
+        );
+    }
+
+    #[test]
+    fn removes_control_keeping_token() {
+        let xml = format!(
+            "<w:p>{}</w:p>",
+            control(
+                r#"<w:tag w:val="CLIENT"/>"#,
+                "<w:r><w:rPr><w:u/></w:rPr><w:t>x</w:t></w:r>"
+            )
+        );
+        assert_eq!(
+            to_tokens(&xml, true),
+            "<w:p><w:r><w:rPr><w:u/></w:rPr><w:t>{{CLIENT}}</w:t></w:r></w:p>"
+        );
+    }
+
+    #[test]
+    fn keeps_non_text_and_unnamed_controls() {
+        let run = "<w:r><w:t>x</w:t></w:r>";
+        let controls = [
+            control(
+                r#"<w:tag w:val="DONE"/><w14:checkbox><w14:checked w14:val="0"/></w14:checkbox>"#,
+                run,
+            ),
+            control(
+                r#"<w:tag w:val="SIGNED"/><w:date w:fullDate="2022-04-01T00:00:00Z"><w:dateFormat w:val="d.M.yyyy"/></w:date>"#,
+                run,
+            ),
+            control(r#"<w:tag w:val="PHOTO"/><w:picture/>"#, run),
+            control(
+                r#"<w:tag w:val="ROW"/>"#,
+                "<w:tr><w:tc><w:p/></w:tc></w:tr>",
+            ),
+            control("<w:id w:val=\"1\"/>", run),
+        ];
+        for xml in controls.iter() {
+            assert_eq!(&to_tokens(xml, true), xml);
+        }
+    }
+
+    #[test]
+    fn turns_innermost_controls_into_tokens() {
+        let inner = control(r#"<w:tag w:val="B"/>"#, "<w:r><w:t>b</w:t></w:r>");
+        let xml = control(r#"<w:tag w:val="A"/>"#, &inner);
+        assert_eq!(
+            to_tokens(&xml, true),
+            control(r#"<w:tag w:val="A"/>"#, "<w:r><w:t>{{B}}</w:t></w:r>")
+        );
+    }
+}
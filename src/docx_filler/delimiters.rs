@@ -0,0 +1,220 @@
+use super::{DocxError, DocxResult};
+use crate::lang;
+use regex::Regex;
+
+/// Default text opening each token, e.g. `{{NAME}}`.
+pub const DEFAULT_OPEN: &str = "{{";
+/// Default text closing each token, e.g. `{{NAME}}`.
+pub const DEFAULT_CLOSE: &str = "}}";
+
+/// Escape character - open delimiter preceded by it is a literal text, not a start of a token (e.g. `\{{`).
+const ESCAPE: &str = "\\";
+/// Character standing for the escaped open delimiter in the loaded template
+/// (Unicode noncharacter, reserved for internal use, so it never comes from the document itself).
+pub const ESCAPED_OPEN: &str = "\u{FDD0}";
+/// Character standing for the open delimiter of the markers hidden from the search for tokens for a while
+/// (e.g. blocks kept in the document as they are, see `hide`).
+const HIDDEN_OPEN: &str = "\u{FDD1}";
+/// Character standing for the open delimiter within the values filled in before the rest of the document
+/// (e.g. in the repeated table rows), so that they are not taken for tokens (see `protect`).
+pub const FILLED_OPEN: &str = "\u{FDD2}";
+/// Characters that cannot be a part of the delimiter, as they are escaped in the document XML.
+const FORBIDDEN_CHARS: [char; 5] = ['<', '>', '&', '"', '\''];
+
+/// Delimiters of the tokens in the template, along with the regular expressions derived from them.
+#[derive(Debug, Clone)]
+pub struct Delimiters {
+    /// text opening each token, e.g. `{{`.
+    open: String,
+    /// text closing each token, e.g. `}}`.
+    close: String,
+    /// matches a single token in the document XML.
+    token_re: Regex,
+    /// matches a single token in the visible text, including the escaped open delimiter (before it is replaced).
+    merge_re: Regex,
+    /// matches the escaped open delimiter in the document XML.
+    escape_re: Regex,
+    /// matches a single literal text looking like a token in the loaded template (see `escape_literals`).
+    literal_re: Regex,
+}
+
+impl Default for Delimiters {
+    fn default() -> Self {
+        Delimiters::new(DEFAULT_OPEN, DEFAULT_CLOSE).unwrap()
+    }
+}
+
+impl Delimiters {
+    /// Creates the delimiters of tokens.
+    ///
+    /// # Errors
+    ///
+    /// Can return Docx::Validation if any of the delimiters is empty or contains characters
+    /// that cannot be used (`<`, `>`, `&`, `"` or `'`), with details in message.
+    pub fn new(open: &str, close: &str) -> DocxResult<Delimiters> {
+        let invalid = |delimiter: &str| {
+            delimiter.trim().is_empty() || delimiter.contains(&FORBIDDEN_CHARS[..])
+        };
+        if invalid(open) || invalid(close) {
+            let args: lang::TrArgVec = vec![
+                ("open".to_string(), open.to_string()),
+                ("close".to_string(), close.to_string()),
+            ];
+            let msg = lang::tr_with_args("docx-filler-invalid-delimiters", &args);
+            return Err(DocxError::Validation(msg));
+        }
+
+        let (open_re, close_re) = (regex::escape(open), regex::escape(close));
+        let escape_re = regex::escape(ESCAPE);
+        let re = |pattern: String| {
+            Regex::new(&pattern).map_err(|err| DocxError::Processing(err.to_string()))
+        };
+        Ok(Delimiters {
+            open: open.to_string(),
+            close: close.to_string(),
+            token_re: re(format!("{}[^<]*?{}", open_re, close_re))?,
+            merge_re: re(format!("(?:{})?{}[^<]*?{}", escape_re, open_re, close_re))?,
+            escape_re: re(format!("{}{}", escape_re, open_re))?,
+            literal_re: re(format!("{}[^<]*?{}", ESCAPED_OPEN, close_re))?,
+        })
+    }
+
+    /// Returns regular expression matching a single token in the document XML.
+    /// Token never spans XML tags, so that it can be looked for in the whole XML of the document part.
+    pub fn token_re(&self) -> &Regex {
+        &self.token_re
+    }
+
+    /// Returns regular expression matching a single token in the visible text of the template,
+    /// including its escape character (see `escape_literals`).
+    pub fn merge_re(&self) -> &Regex {
+        &self.merge_re
+    }
+
+    /// Returns regular expression matching a single literal text looking like a token (e.g. `\{{code}}`)
+    /// in the loaded template, with its open delimiter escaped by `escape_literals`.
+    pub fn literal_re(&self) -> &Regex {
+        &self.literal_re
+    }
+
+    /// Returns the text opening each token, e.g. `{{`.
+    pub fn open(&self) -> &str {
+        &self.open
+    }
+
+    /// Returns the text closing each token, e.g. `}}`.
+    pub fn close(&self) -> &str {
+        &self.close
+    }
+
+    /// Returns the text of the token between its delimiters (e.g. `NAME` for `{{NAME}}`).
+    pub fn inner<'a>(&self, token: &'a str) -> &'a str {
+        token
+            .strip_prefix(&self.open)
+            .and_then(|inner| inner.strip_suffix(&self.close))
+            .unwrap_or(token)
+    }
+
+    /// Encloses the text into the delimiters, making it a token (e.g. `{{NAME}}` for `NAME`).
+    pub fn wrap(&self, inner: &str) -> String {
+        [&self.open, inner, &self.close].concat()
+    }
+
+    /// Hides the escaped open delimiters of the template XML (e.g. `\{{`), so that they are not taken for tokens.
+    pub fn escape_literals(&self, xml: &str) -> String {
+        self.escape_re.replace_all(xml, ESCAPED_OPEN).to_string()
+    }
+
+    /// Turns the escaped open delimiters hidden by `escape_literals` back into the escaped ones (e.g. `\{{`),
+    /// so that they stay literal in the generated document used as a template again.
+    pub fn restore_escapes(&self, xml: &str) -> String {
+        xml.replace(ESCAPED_OPEN, &self.escaped_open())
+    }
+
+    /// Returns the escaped open delimiter, e.g. `\{{`.
+    pub fn escaped_open(&self) -> String {
+        [ESCAPE, &self.open].concat()
+    }
+
+    /// Hides the open delimiters of the value filled in before the rest of the document, so that the value
+    /// is never filled again (e.g. `{{NAME}}` typed as an item of the list). The compiled document turns them
+    /// back into the open delimiters when it is rendered.
+    pub fn protect(&self, value: &str) -> String {
+        value.replace(&self.open, FILLED_OPEN)
+    }
+
+    /// Hides the token (e.g. block marker to be kept as it is) from the search for tokens, until it is revealed.
+    pub fn hide(&self, token: &str) -> String {
+        match token.strip_prefix(&self.open) {
+            Some(rest) => [HIDDEN_OPEN, rest].concat(),
+            None => token.to_string(),
+        }
+    }
+
+    /// Reveals the tokens hidden by `hide`.
+    pub fn reveal(&self, xml: &str) -> String {
+        xml.replace(HIDDEN_OPEN, &self.open)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tokens<'a>(delimiters: &Delimiters, xml: &'a str) -> Vec<&'a str> {
+        delimiters
+            .token_re()
+            .find_iter(xml)
+            .map(|found| found.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn finds_tokens_of_custom_delimiters() {
+        let delimiters = Delimiters::new("[[", "]]").unwrap();
+        let xml = "<w:t>[[A]] {{B}} [[C.*]]</w:t><w:t>[[D</w:t><w:t>]]</w:t>";
+        assert_eq!(tokens(&delimiters, xml), vec!["[[A]]", "[[C.*]]"]);
+        assert_eq!(delimiters.inner("[[A]]"), "A");
+        assert_eq!(delimiters.wrap("A"), "[[A]]");
+    }
+
+    #[test]
+    fn rejects_invalid_delimiters() {
+        assert!(Delimiters::new("", "}}").is_err());
+        assert!(Delimiters::new("<<", ">>").is_err());
+        assert!(Delimiters::new("${", " ").is_err());
+        assert!(Delimiters::new("${", "}").is_ok());
+    }
+
+    #[test]
+    fn keeps_escaped_literals() {
+        let delimiters = Delimiters::new("${", "}").unwrap();
+        let escaped = delimiters.escape_literals(r"\${CODE} ${NAME}");
+        assert_eq!(tokens(&delimiters, &escaped), vec!["${NAME}"]);
+        assert_eq!(
+            delimiters
+                .literal_re()
+                .find_iter(&escaped)
+                .map(|found| found.as_str())
+                .collect::<Vec<_>>(),
+            vec![[ESCAPED_OPEN, "CODE}"].concat()]
+        );
+        assert_eq!(delimiters.restore_escapes(&escaped), r"\${CODE} ${NAME}");
+    }
+
+    #[test]
+    fn merges_escaped_tokens() {
+        let delimiters = Delimiters::default();
+        let found = delimiters.merge_re().find(r"a \{{B}}").unwrap();
+        assert_eq!(found.as_str(), r"\{{B}}");
+    }
+
+    #[test]
+    fn hides_and_protects_tokens() {
+        let delimiters = Delimiters::default();
+        let hidden = delimiters.hide("{{#if A}}");
+        assert!(tokens(&delimiters, &hidden).is_empty());
+        assert_eq!(delimiters.reveal(&hidden), "{{#if A}}");
+        assert!(tokens(&delimiters, &delimiters.protect("{{A}}")).is_empty());
+    }
+}
@@ -0,0 +1,273 @@
+use super::delimiters::{Delimiters, ESCAPED_OPEN, FILLED_OPEN};
+use super::formats::{self, TokenParts};
+use super::{xml, FillMode, TokenPackArg, ValuePackArg};
+use regex::Regex;
+use std::collections::HashMap;
+
+lazy_static! {
+    /// Matches the characters standing for the open delimiters in the literal text (see `Delimiters`).
+    static ref SENTINEL_RE: Regex = Regex::new(&[ESCAPED_OPEN, "|", FILLED_OPEN].concat()).unwrap();
+}
+
+/// Output the compiled XML is rendered into.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Output {
+    /// fragment of the document filled before the rest of it (e.g. repeated table row) - open delimiters
+    /// of the filled in values, as well as the escaped ones, stay hidden, so they are not taken for tokens later.
+    Fragment,
+    /// the generated document, filled in the fill mode (escaped open delimiters become literal text,
+    /// or stay escaped in partial fill mode).
+    Document(FillMode),
+}
+
+/// Single piece of the compiled XML.
+#[derive(Debug, Clone)]
+enum Segment {
+    /// XML copied into the output as it is.
+    Literal(String),
+    /// escaped open delimiter of the template (e.g. `\{{`).
+    Escaped,
+    /// open delimiter within a value filled in before (see `Delimiters::protect`).
+    Filled,
+    /// token to be filled in with its value.
+    Slot(Slot),
+}
+
+/// Token of the compiled XML, with everything needed to fill it in known in advance.
+#[derive(Debug, Clone)]
+struct Slot {
+    /// text of the token as written in the template, e.g. `{{NAME|upper}}`.
+    text: String,
+    /// parts of the token - base token, default value and formatters.
+    parts: TokenParts,
+    /// XML context of the token, determining how its value is escaped.
+    context: xml::XmlContext,
+}
+
+/// Document XML (or its fragment) compiled into literal segments and token slots, so that it can be filled
+/// in a single pass for each set of values. Values are never searched for tokens again, so a value containing
+/// text of another token (e.g. `{{NAME}}`) is filled in just as it is.
+#[derive(Debug, Clone)]
+pub struct CompiledXml {
+    /// segments of the XML, in the document order.
+    segments: Vec<Segment>,
+    /// delimiters of the tokens in the template.
+    delimiters: Delimiters,
+}
+
+impl CompiledXml {
+    /// Compiles the XML of the document part (or its fragment), looking for the tokens in the whole XML.
+    ///
+    /// # Arguments
+    ///
+    /// * `xml` - contents of the document part
+    /// * `delimiters` - delimiters of the tokens in the template
+    pub fn compile(xml: &str, delimiters: &Delimiters) -> CompiledXml {
+        let mut compiled = CompiledXml {
+            segments: Default::default(),
+            delimiters: delimiters.clone(),
+        };
+        let mut last = 0;
+        for found in delimiters.token_re().find_iter(xml) {
+            // text swallowing the escaped open delimiter (e.g. `{{A \{{B}}`) is not a token
+            if SENTINEL_RE.is_match(found.as_str()) {
+                continue;
+            }
+            compiled.push_literal(&xml[last..found.start()]);
+            compiled.segments.push(Segment::Slot(Slot {
+                text: found.as_str().to_string(),
+                parts: formats::split_token(found.as_str(), delimiters),
+                context: xml::context_at(xml, found.start()),
+            }));
+            last = found.end();
+        }
+        compiled.push_literal(&xml[last..]);
+        compiled
+    }
+
+    /// Checks whether the compiled XML contains a token matching the predicate.
+    pub fn has_token(&self, predicate: impl Fn(&str) -> bool) -> bool {
+        self.segments
+            .iter()
+            .any(|segment| matches!(segment, Segment::Slot(slot) if predicate(&slot.text)))
+    }
+
+    /// Fills the tokens with the specified values in a single pass. Default value of the token
+    /// (e.g. `{{NAME?=guest}}`) is used in place of the empty value, formatters of the token
+    /// (e.g. `{{NAME|upper}}`) are applied to its value, and values are escaped as needed by the place
+    /// in XML where each of the tokens is. Tokens without value are kept as they are.
+    ///
+    /// # Arguments
+    ///
+    /// * `tokens` - vector of tokens to be replaced
+    /// * `values` - vector of values to be filled in place of tokens
+    /// * `output` - output the XML is rendered into
+    pub fn render(&self, tokens: TokenPackArg, values: ValuePackArg, output: Output) -> String {
+        assert_eq!(tokens.len(), values.len());
+        let mut positions: HashMap<&str, usize> = HashMap::with_capacity(tokens.len());
+        for (i, token) in tokens.iter().enumerate() {
+            positions.entry(token.as_str()).or_insert(i);
+        }
+
+        let open = self.delimiters.open();
+        let escaped_open = self.delimiters.escaped_open();
+        let (escaped, filled) = match output {
+            Output::Fragment => (ESCAPED_OPEN, FILLED_OPEN),
+            Output::Document(FillMode::Complete) => (open, open),
+            Output::Document(FillMode::Partial) => (escaped_open.as_str(), open),
+        };
+        let mut rendered = String::with_capacity(self.len());
+        for segment in self.segments.iter() {
+            match segment {
+                Segment::Literal(text) => rendered.push_str(text),
+                Segment::Escaped => rendered.push_str(escaped),
+                Segment::Filled => rendered.push_str(filled),
+                Segment::Slot(slot) => match positions.get(slot.parts.token.as_str()) {
+                    Some(&i) => {
+                        let value = match slot.context {
+                            xml::XmlContext::RunText => xml::unescape_sequences(&values[i].text()),
+                            _ => values[i].text(),
+                        };
+                        let value = slot.parts.fill(&value);
+                        let value = xml::escape(&value, slot.context);
+                        match output {
+                            Output::Fragment => rendered.push_str(&self.delimiters.protect(&value)),
+                            Output::Document(_) => rendered.push_str(&value),
+                        }
+                    }
+                    None => rendered.push_str(&slot.text),
+                },
+            }
+        }
+        rendered
+    }
+
+    /// Adds the literal text, with the characters standing for the open delimiters as separate segments.
+    fn push_literal(&mut self, text: &str) {
+        let mut last = 0;
+        for found in SENTINEL_RE.find_iter(text) {
+            if found.start() > last {
+                self.segments
+                    .push(Segment::Literal(text[last..found.start()].to_string()));
+            }
+            self.segments.push(match found.as_str() {
+                ESCAPED_OPEN => Segment::Escaped,
+                _ => Segment::Filled,
+            });
+            last = found.end();
+        }
+        if last < text.len() {
+            self.segments
+                .push(Segment::Literal(text[last..].to_string()));
+        }
+    }
+
+    /// Returns the length of the compiled XML (tokens included), the estimate of the rendered length.
+    fn len(&self) -> usize {
+        self.segments
+            .iter()
+            .map(|segment| match segment {
+                Segment::Literal(text) => text.len(),
+                Segment::Slot(slot) => slot.text.len(),
+                _ => self.delimiters.open().len(),
+            })
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::Value;
+    use super::*;
+
+    fn render(xml: &str, tokens: &[&str], values: &[&str], output: Output) -> String {
+        let tokens: Vec<String> = tokens.iter().map(|token| token.to_string()).collect();
+        let values: Vec<Value> = values
+            .iter()
+            .map(|value| Value::Text(value.to_string()))
+            .collect();
+        CompiledXml::compile(xml, &Delimiters::default()).render(&tokens, &values, output)
+    }
+
+    #[test]
+    fn escapes_default_value_once() {
+        let xml = r#"<w:t>{{A?=Smith &amp; &lt;Sons&gt;}}</w:t><w:x w:val="{{A?=&quot;Ltd&quot; &amp; Co}}"/>"#;
+        assert_eq!(
+            render(xml, &["{{A}}"], &[""], Output::Document(FillMode::Complete)),
+            r#"<w:t>Smith &amp; &lt;Sons&gt;</w:t><w:x w:val="&quot;Ltd&quot; &amp; Co"/>"#
+        );
+    }
+
+    #[test]
+    fn keeps_values_with_token_text_literal() {
+        let xml = "<w:t>{{A}} {{B}}</w:t>";
+        let tokens = ["{{A}}", "{{B}}"];
+        let values = ["{{B}}", "{{A}}"];
+        assert_eq!(
+            render(xml, &tokens, &values, Output::Document(FillMode::Complete)),
+            "<w:t>{{B}} {{A}}</w:t>"
+        );
+        assert_eq!(
+            render(xml, &tokens, &values, Output::Fragment),
+            ["<w:t>", FILLED_OPEN, "B}} ", FILLED_OPEN, "A}}</w:t>"].concat()
+        );
+    }
+
+    #[test]
+    fn renders_escaped_and_filled_open_delimiters() {
+        let xml = [
+            "<w:t>",
+            ESCAPED_OPEN,
+            "A}} ",
+            FILLED_OPEN,
+            "B}} {{C ",
+            ESCAPED_OPEN,
+            "D}} {{C}}</w:t>",
+        ]
+        .concat();
+        let rendered = |output| render(&xml, &["{{C}}"], &["c"], output);
+        assert_eq!(
+            rendered(Output::Fragment),
+            [
+                "<w:t>",
+                ESCAPED_OPEN,
+                "A}} ",
+                FILLED_OPEN,
+                "B}} {{C ",
+                ESCAPED_OPEN,
+                "D}} c</w:t>"
+            ]
+            .concat()
+        );
+        assert_eq!(
+            rendered(Output::Document(FillMode::Complete)),
+            "<w:t>{{A}} {{B}} {{C {{D}} c</w:t>"
+        );
+        assert_eq!(
+            rendered(Output::Document(FillMode::Partial)),
+            r"<w:t>\{{A}} {{B}} {{C \{{D}} c</w:t>"
+        );
+    }
+
+    #[test]
+    fn escapes_value_by_context_of_token() {
+        let xml = concat!(
+            r#"<w:p w:x="{{A}}"><w:r><w:t>{{A}}</w:t></w:r></w:p>"#,
+            r#"<Relationship Id="rId1" Target="{{A}}"/>"#
+        );
+        let value = r#"<a & "b">\n'c'"#;
+        assert_eq!(
+            render(
+                xml,
+                &["{{A}}"],
+                &[value],
+                Output::Document(FillMode::Complete)
+            ),
+            concat!(
+                r#"<w:p w:x="&lt;a &amp; &quot;b&quot;&gt;\n&apos;c&apos;">"#,
+                r#"<w:r><w:t>&lt;a &amp; "b"&gt;</w:t><w:br/><w:t>'c'</w:t></w:r></w:p>"#,
+                r#"<Relationship Id="rId1" Target="%3Ca%20&amp;%20%22b%22%3E%5Cn'c'"/>"#
+            )
+        );
+    }
+}
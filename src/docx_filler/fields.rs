@@ -0,0 +1,244 @@
+use super::delimiters::Delimiters;
+use super::xml;
+use regex::Regex;
+use std::ops::Range;
+
+lazy_static! {
+    /// Matches the simple field element with its instruction, e.g. `<w:fldSimple w:instr=" MERGEFIELD Name ">`.
+    static ref SIMPLE_FIELD_RE: Regex =
+        Regex::new(r#"<w:fldSimple\s[^>]*?\bw:instr="([^"]*)"[^>]*?(?:/>|>(?s:.*?)</w:fldSimple>)"#).unwrap();
+    /// Matches the field character of a complex field, e.g. `<w:fldChar w:fldCharType="begin"/>`.
+    static ref FIELD_CHAR_RE: Regex = Regex::new(r#"<w:fldChar\s[^>]*?\bw:fldCharType="(\w+)""#).unwrap();
+    /// Matches the instruction text of a complex field.
+    static ref INSTR_TEXT_RE: Regex = Regex::new(r"<w:instrText(?:\s[^>]*)?>([^<]*)</w:instrText>").unwrap();
+    /// Matches the merge field instruction, with the name of the field (quoted, or without spaces),
+    /// e.g. `MERGEFIELD "First name" \* Upper`.
+    static ref MERGE_FIELD_RE: Regex = Regex::new(r#"^\s*MERGEFIELD\s+(?:"([^"]+)"|(\S+))"#).unwrap();
+    /// Matches the format switch of the field, e.g. `\* Upper`.
+    static ref FORMAT_SWITCH_RE: Regex = Regex::new(r"\\\*\s*(\w+)").unwrap();
+    /// Matches the run properties element.
+    static ref RUN_PROPERTIES_RE: Regex = Regex::new(r"<w:rPr>(?s:.*?)</w:rPr>|<w:rPr/>").unwrap();
+}
+
+/// Case conversion switches of the merge fields, along with the formatters doing the same.
+const CASE_FORMATTERS: [(&str, &str); 4] = [
+    ("upper", "upper"),
+    ("lower", "lower"),
+    ("caps", "title"),
+    ("firstcap", "title"),
+];
+
+/// Turns the mail merge fields of the document part (`MERGEFIELD Name`, both simple and complex ones)
+/// into tokens (e.g. `{{Name}}`), so that they get filled the same way as the text tokens.
+/// Field code is removed along with its cached result, the token keeps formatting of the result.
+/// Case conversion switches (e.g. `\* Upper`) are turned into formatters (e.g. `{{Name|upper}}`).
+/// Fields nested in other fields, or spanning several paragraphs are kept as they are.
+///
+/// # Arguments
+///
+/// * `xml` - contents of the document part
+/// * `delimiters` - delimiters of the tokens in the template
+pub fn merge_fields_to_tokens(xml: &str, delimiters: &Delimiters) -> String {
+    let mut replacements: Vec<(Range<usize>, String)> = Default::default();
+    for found in SIMPLE_FIELD_RE.captures_iter(xml) {
+        let field = found.get(0).unwrap();
+        if let Some(token) = field_token(&found[1], delimiters) {
+            replacements.push((field.range(), token_run(field.as_str(), &token)));
+        }
+    }
+    for (field, instruction) in complex_fields(xml) {
+        if let Some(token) = field_token(&instruction, delimiters) {
+            let run = token_run(result_of(&xml[field.clone()]), &token);
+            replacements.push((field, run));
+        }
+    }
+    replacements.sort_by_key(|(range, _)| range.start);
+
+    let mut output = xml.to_string();
+    for (range, replacement) in replacements.into_iter().rev() {
+        output.replace_range(range, &replacement);
+    }
+    output
+}
+
+/// Returns the token for the field instruction, if it is a merge field (e.g. `{{Name}}` for `MERGEFIELD Name`).
+fn field_token(instruction: &str, delimiters: &Delimiters) -> Option<String> {
+    let instruction = xml::unescape(instruction);
+    let found = MERGE_FIELD_RE.captures(&instruction)?;
+    let name = found.get(1).or_else(|| found.get(2))?.as_str();
+    let formatter = FORMAT_SWITCH_RE
+        .captures_iter(&instruction)
+        .find_map(|switch| {
+            CASE_FORMATTERS
+                .iter()
+                .find(|(known, _)| known.eq_ignore_ascii_case(&switch[1]))
+                .map(|(_, formatter)| *formatter)
+        });
+    match formatter {
+        Some(formatter) => Some(delimiters.wrap(&[name, "|", formatter].concat())),
+        None => Some(delimiters.wrap(name)),
+    }
+}
+
+/// Creates the run with the token, formatted the same way as the first run of the field XML (if there is any).
+fn token_run(field: &str, token: &str) -> String {
+    let properties = RUN_PROPERTIES_RE
+        .find(field)
+        .map(|found| found.as_str())
+        .unwrap_or_default();
+    let token = xml::escape(token, xml::XmlContext::RunText);
+    format!("<w:r>{}<w:t>{}</w:t></w:r>", properties, token)
+}
+
+/// Returns the part of the complex field XML with the field result (after the `separate` field character),
+/// or the whole field, if it has no result.
+fn result_of(field: &str) -> &str {
+    FIELD_CHAR_RE
+        .captures_iter(field)
+        .find(|found| &found[1] == "separate")
+        .map_or(field, |found| &field[found.get(0).unwrap().end()..])
+}
+
+/// Lists the complex fields (from the run with the `begin` field character to the run with the `end` one)
+/// that are not nested and stay within a single paragraph, along with their instructions.
+fn complex_fields(xml: &str) -> Vec<(Range<usize>, String)> {
+    let mut fields: Vec<(Range<usize>, String)> = Default::default();
+    let mut depth: usize = 0;
+    let mut start: usize = 0;
+    let mut instruction = String::new();
+    let mut nested = false;
+    for run in xml::element_ranges(xml, "w:r") {
+        let run_xml = &xml[run.clone()];
+        for found in INSTR_TEXT_RE.captures_iter(run_xml) {
+            if depth == 1 {
+                instruction.push_str(&found[1]);
+            }
+        }
+        let char_type = match FIELD_CHAR_RE.captures(run_xml) {
+            Some(found) => found[1].to_string(),
+            None => continue,
+        };
+        match char_type.as_str() {
+            "begin" if depth == 0 => {
+                depth = 1;
+                start = run.start;
+                instruction.clear();
+                nested = false;
+            }
+            "begin" => {
+                depth += 1;
+                nested = true;
+            }
+            "end" if depth == 1 => {
+                depth = 0;
+                let field = start..run.end;
+                if !nested && xml::is_balanced(&xml[field.clone()]) {
+                    fields.push((field, instruction.clone()));
+                }
+            }
+            "end" => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+    }
+    fields
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn to_tokens(xml: &str) -> String {
+        merge_fields_to_tokens(xml, &Delimiters::default())
+    }
+
+    fn field_char(kind: &str) -> String {
+        format!(r#"<w:r><w:fldChar w:fldCharType="{}"/></w:r>"#, kind)
+    }
+
+    fn complex_field(instruction: &str, result: &str) -> String {
+        [
+            &field_char("begin"),
+            "<w:r><w:rPr><w:i/></w:rPr><w:instrText xml:space=\"preserve\">",
+            instruction,
+            "</w:instrText></w:r>",
+            &field_char("separate"),
+            result,
+            &field_char("end"),
+        ]
+        .concat()
+    }
+
+    #[test]
+    fn turns_simple_field_into_token() {
+        let xml = r#"<w:p><w:fldSimple w:instr=" MERGEFIELD Name \* MERGEFORMAT "><w:r><w:rPr><w:b/></w:rPr><w:t>«Name»</w:t></w:r></w:fldSimple></w:p>"#;
+        assert_eq!(
+            to_tokens(xml),
+            "<w:p><w:r><w:rPr><w:b/></w:rPr><w:t>{{Name}}</w:t></w:r></w:p>"
+        );
+        let xml =
+            r#"<w:p><w:fldSimple w:instr=" MERGEFIELD &quot;First name&quot; \* Upper "/></w:p>"#;
+        assert_eq!(
+            to_tokens(xml),
+            "<w:p><w:r><w:t>{{First name|upper}}</w:t></w:r></w:p>"
+        );
+    }
+
+    #[test]
+    fn turns_complex_field_into_token() {
+        let field = complex_field(
+            " MERGEFIELD  City \\* Caps ",
+            "<w:r><w:rPr><w:b/></w:rPr><w:t>«City»</w:t></w:r>",
+        );
+        let xml = format!(
+            "<w:p><w:r><w:t>In </w:t></w:r>{}<w:r><w:t>.</w:t></w:r></w:p>",
+            field
+        );
+        assert_eq!(
+            to_tokens(&xml),
+            "<w:p><w:r><w:t>In </w:t></w:r><w:r><w:rPr><w:b/></w:rPr><w:t>{{City|title}}</w:t></w:r><w:r><w:t>.</w:t></w:r></w:p>"
+        );
+    }
+
+    #[test]
+    fn turns_complex_field_without_result_into_token() {
+        let xml = [
+            "<w:p>",
+            &field_char("begin"),
+            "<w:r><w:rPr><w:i/></w:rPr><w:instrText>MERGEFIELD Name \\* lower</w:instrText></w:r>",
+            &field_char("end"),
+            "</w:p>",
+        ]
+        .concat();
+        assert_eq!(
+            to_tokens(&xml),
+            "<w:p><w:r><w:rPr><w:i/></w:rPr><w:t>{{Name|lower}}</w:t></w:r></w:p>"
+        );
+    }
+
+    #[test]
+    fn keeps_other_nested_and_split_fields() {
+        let page = format!(
+            "<w:p>{}</w:p>",
+            complex_field(" PAGE ", "<w:r><w:t>1</w:t></w:r>")
+        );
+        assert_eq!(to_tokens(&page), page);
+        let simple = r#"<w:p><w:fldSimple w:instr=" DATE "><w:r><w:t>1.4.2022</w:t></w:r></w:fldSimple></w:p>"#;
+        assert_eq!(to_tokens(simple), simple);
+
+        let nested = format!(
+            "<w:p>{}</w:p>",
+            complex_field(
+                " MERGEFIELD A ",
+                &complex_field(" MERGEFIELD B ", "<w:r><w:t>b</w:t></w:r>")
+            )
+        );
+        assert_eq!(to_tokens(&nested), nested);
+
+        let split = complex_field(
+            " MERGEFIELD A ",
+            "<w:r><w:t>a</w:t></w:r></w:p><w:p><w:r><w:t>b</w:t></w:r>",
+        );
+        let split = format!("<w:p>{}</w:p>", split);
+        assert_eq!(to_tokens(&split), split);
+    }
+}
@@ -0,0 +1,310 @@
+use super::types::{self, TokenType};
+use super::{delimiters::Delimiters, xml, DocxError, DocxResult};
+use crate::lang;
+use chrono::format::{Item, StrftimeItems};
+use chrono::{NaiveDate, NaiveDateTime};
+use std::fmt::Write;
+
+/// Separator of the formatters applied to the token value, e.g. `{{NAME|upper}}`.
+const FORMAT_SEPARATOR: char = '|';
+/// Separator of the formatter and its argument, e.g. `{{AMOUNT|number:2}}`.
+const ARGUMENT_SEPARATOR: char = ':';
+/// Separator of the token name and its default value, e.g. `{{TITLE?=Mr.}}`.
+const DEFAULT_SEPARATOR: &str = "?=";
+
+/// Characters accepted as quotes around the formatter argument (including the typographic ones used by Word).
+const QUOTES: [char; 4] = ['"', '\u{201C}', '\u{201D}', '\u{201E}'];
+
+/// Date format used by `date` formatter, if none is given explicitly.
+const DEFAULT_DATE_FORMAT: &str = "%Y-%m-%d";
+/// Formats accepted for the date values to be reformatted by `date` formatter.
+const INPUT_DATE_FORMATS: [&str; 4] = ["%Y-%m-%d", "%d.%m.%Y", "%d. %m. %Y", "%Y/%m/%d"];
+/// Formats accepted for the date and time values to be reformatted by `date` formatter.
+const INPUT_DATETIME_FORMATS: [&str; 2] = ["%Y-%m-%d %H:%M", "%Y-%m-%d %H:%M:%S"];
+
+/// Formatter changing the shape of the value filled in place of the token.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Formatter {
+    /// name of the formatter, e.g. `upper`.
+    name: String,
+    /// optional argument of the formatter, e.g. number of decimals for `number`.
+    argument: Option<String>,
+}
+
+/// Parts of the token as written in the document, e.g. `{{NAME:email?=guest|upper}}`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TokenParts {
+    /// base token the value is entered for, e.g. `{{NAME}}`.
+    pub token: String,
+    /// type of the value declared in the template, e.g. `email`.
+    pub kind: Option<TokenType>,
+    /// default value used when the value of the token is empty, e.g. `guest`.
+    pub default: Option<String>,
+    /// formatters applied to the value, e.g. `upper`.
+    pub formatters: Vec<Formatter>,
+}
+
+impl TokenParts {
+    /// Returns the value to be filled in place of the token - default value is used in place of the empty one,
+    /// and the formatters are applied.
+    pub fn fill(&self, value: &str) -> String {
+        match &self.default {
+            Some(default) if value.trim().is_empty() => apply(default, &self.formatters),
+            _ => apply(value, &self.formatters),
+        }
+    }
+}
+
+/// Splits the token into its base token, type, default value and formatters applied to it
+/// (e.g. `{{NAME}}`, `email`, `guest` and `upper` for the `{{NAME:email?=guest|upper}}`).
+/// Default value and formatter arguments are unescaped, as the token is taken from the XML text.
+pub fn split_token(token: &str, delimiters: &Delimiters) -> TokenParts {
+    let inner = delimiters.inner(token);
+    let mut parts = split_outside_quotes(inner, FORMAT_SEPARATOR).into_iter();
+    let mut name_default = parts
+        .next()
+        .unwrap_or_default()
+        .splitn(2, DEFAULT_SEPARATOR);
+    let (name, kind) = types::split_type(name_default.next().unwrap_or_default());
+    let default = name_default
+        .next()
+        .map(|default| unquote(&xml::unescape(default.trim())).to_string());
+    let formatters = parts
+        .map(|part| {
+            let mut name_arg = part.splitn(2, ARGUMENT_SEPARATOR);
+            Formatter {
+                name: name_arg.next().unwrap_or_default().trim().to_string(),
+                argument: name_arg
+                    .next()
+                    .map(|arg| unquote(&xml::unescape(arg.trim())).to_string()),
+            }
+        })
+        .collect();
+    TokenParts {
+        token: delimiters.wrap(name),
+        kind,
+        default,
+        formatters,
+    }
+}
+
+/// Returns the base token of the (possibly formatted) token, e.g. `{{NAME}}` for `{{NAME:email?=guest|upper}}`.
+pub fn base_token(token: &str, delimiters: &Delimiters) -> String {
+    split_token(token, delimiters).token
+}
+
+/// Verifies that all the formatters of the token are known, and have valid arguments.
+///
+/// # Errors
+///
+/// Can return Docx::Validation on failure, with details in message.
+pub fn validate_token(token: &str, delimiters: &Delimiters) -> DocxResult<()> {
+    for formatter in split_token(token, delimiters).formatters {
+        let valid = match (formatter.name.as_str(), &formatter.argument) {
+            ("upper", None) | ("lower", None) | ("title", None) => true,
+            ("number", None) => true,
+            ("number", Some(decimals)) => decimals.parse::<usize>().is_ok(),
+            ("date", None) => true,
+            ("date", Some(format)) => is_valid_date_format(format),
+            _ => false,
+        };
+        if !valid {
+            let args: lang::TrArgVec = vec![("token".to_string(), token.to_string())];
+            let msg = lang::tr_with_args("docx-filler-unknown-format", &args);
+            return Err(DocxError::Validation(msg));
+        }
+    }
+    Ok(())
+}
+
+/// Applies the formatters to the value, one after another.
+/// Values that cannot be formatted (e.g. text that is not a number for `number` formatter) are kept as they are.
+fn apply(value: &str, formatters: &[Formatter]) -> String {
+    formatters
+        .iter()
+        .fold(value.to_string(), |value, formatter| {
+            formatter.apply(&value)
+        })
+}
+
+impl Formatter {
+    /// Applies the formatter to the value.
+    fn apply(&self, value: &str) -> String {
+        let argument = self.argument.as_deref();
+        let formatted = match self.name.as_str() {
+            "upper" => Some(value.to_uppercase()),
+            "lower" => Some(value.to_lowercase()),
+            "title" => Some(title_case(value)),
+            "number" => format_number(value, argument),
+            "date" => format_date(value, argument),
+            _ => None,
+        };
+        formatted.unwrap_or_else(|| value.to_string())
+    }
+}
+
+/// Capitalizes first letter of each word, turning the rest of letters to lower case.
+fn title_case(value: &str) -> String {
+    let mut output = String::with_capacity(value.len());
+    let mut word_start = true;
+    for ch in value.chars() {
+        if word_start {
+            output.extend(ch.to_uppercase());
+        } else {
+            output.extend(ch.to_lowercase());
+        }
+        word_start = ch.is_whitespace() || ch == '-';
+    }
+    output
+}
+
+/// Formats the number value with the fixed number of decimals (none by default).
+/// Decimal comma is accepted on input as well as the decimal point.
+fn format_number(value: &str, decimals: Option<&str>) -> Option<String> {
+    let decimals: usize = decimals.unwrap_or("0").parse().ok()?;
+    let number: f64 = value
+        .trim()
+        .replace(char::is_whitespace, "")
+        .replace(',', ".")
+        .parse()
+        .ok()?;
+    Some(format!("{:.*}", decimals, number))
+}
+
+/// Formats the date (or date and time) value by the date format (see `chrono::format::strftime` for details).
+fn format_date(value: &str, format: Option<&str>) -> Option<String> {
+    let format = format.unwrap_or(DEFAULT_DATE_FORMAT);
+    if !is_valid_date_format(format) {
+        return None;
+    }
+    let datetime = INPUT_DATETIME_FORMATS
+        .iter()
+        .find_map(|input| NaiveDateTime::parse_from_str(value.trim(), input).ok())
+        .or_else(|| parse_date(value)?.and_hms_opt(0, 0, 0))?;
+    let mut formatted = String::new();
+    write!(formatted, "{}", datetime.format(format)).ok()?;
+    Some(formatted)
+}
+
+/// Parses the date value, written in one of the accepted formats.
+pub fn parse_date(value: &str) -> Option<NaiveDate> {
+    INPUT_DATE_FORMATS
+        .iter()
+        .find_map(|format| NaiveDate::parse_from_str(value.trim(), format).ok())
+}
+
+/// Checks whether the date format can be used for formatting the dates.
+fn is_valid_date_format(format: &str) -> bool {
+    !StrftimeItems::new(format).any(|item| matches!(item, Item::Error))
+}
+
+/// Removes the double quotes around the text, if there are any.
+fn unquote(text: &str) -> &str {
+    text.strip_prefix(&QUOTES[..])
+        .and_then(|text| text.strip_suffix(&QUOTES[..]))
+        .unwrap_or(text)
+}
+
+/// Splits the text by separator, ignoring separators enclosed in double quotes.
+fn split_outside_quotes(text: &str, separator: char) -> Vec<&str> {
+    let mut parts: Vec<&str> = Default::default();
+    let mut quoted = false;
+    let mut last = 0;
+    for (i, ch) in text.char_indices() {
+        if QUOTES.contains(&ch) {
+            quoted = !quoted;
+        } else if ch == separator && !quoted {
+            parts.push(&text[last..i]);
+            last = i + ch.len_utf8();
+        }
+    }
+    parts.push(&text[last..]);
+    parts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn formatter(name: &str, argument: Option<&str>) -> Formatter {
+        Formatter {
+            name: name.to_string(),
+            argument: argument.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn splits_token_into_parts() {
+        let parts = split_token(
+            r#"{{NAME:email?="a|b"|upper|date:"%d. %m."}}"#,
+            &Delimiters::default(),
+        );
+        assert_eq!(
+            parts,
+            TokenParts {
+                token: "{{NAME}}".to_string(),
+                kind: Some(TokenType::Email),
+                default: Some("a|b".to_string()),
+                formatters: vec![formatter("upper", None), formatter("date", Some("%d. %m."))],
+            }
+        );
+    }
+
+    #[test]
+    fn splits_plain_token() {
+        let parts = split_token("{{NAME}}", &Delimiters::default());
+        assert_eq!(parts.token, "{{NAME}}");
+        assert_eq!((parts.kind, parts.default), (None, None));
+        assert!(parts.formatters.is_empty());
+    }
+
+    #[test]
+    fn accepts_typographic_quotes() {
+        let parts = split_token("{{D|date:\u{201E}%Y\u{201C}}}", &Delimiters::default());
+        assert_eq!(parts.formatters, vec![formatter("date", Some("%Y"))]);
+    }
+
+    #[test]
+    fn unescapes_default_and_arguments() {
+        let parts = split_token(
+            "{{A?=Smith &amp; &lt;Sons&gt; &quot;Ltd&quot;|date:&quot;%d&lt;%m&quot;}}",
+            &Delimiters::default(),
+        );
+        assert_eq!(parts.default.as_deref(), Some(r#"Smith & <Sons> "Ltd""#));
+        assert_eq!(parts.formatters, vec![formatter("date", Some("%d<%m"))]);
+        assert_eq!(parts.fill(""), r#"Smith & <Sons> "Ltd""#);
+    }
+
+    #[test]
+    fn validates_formatters() {
+        let delimiters = Delimiters::default();
+        assert!(validate_token("{{A|upper|number:2|date:%d.%m.%Y}}", &delimiters).is_ok());
+        assert!(validate_token("{{A|number:x}}", &delimiters).is_err());
+        assert!(validate_token("{{A|upper:1}}", &delimiters).is_err());
+        assert!(validate_token("{{A|unknown}}", &delimiters).is_err());
+    }
+
+    #[test]
+    fn fills_formatted_value_or_default() {
+        let parts = split_token("{{A?=mr. smith|title}}", &Delimiters::default());
+        assert_eq!(parts.fill("JOHN DOE-ROE"), "John Doe-Roe");
+        assert_eq!(parts.fill(" "), "Mr. Smith");
+    }
+
+    #[test]
+    fn formats_numbers_and_dates() {
+        assert_eq!(
+            apply("1 234,5", &[formatter("number", Some("2"))]),
+            "1234.50"
+        );
+        assert_eq!(apply("abc", &[formatter("number", None)]), "abc");
+        assert_eq!(
+            apply("1.4.2022", &[formatter("date", Some("%d/%m/%Y"))]),
+            "01/04/2022"
+        );
+        assert_eq!(
+            apply("2022-04-01 13:05", &[formatter("date", Some("%H:%M"))]),
+            "13:05"
+        );
+    }
+}
@@ -0,0 +1,602 @@
+use super::delimiters::Delimiters;
+use super::{formats, xml, DocxError, DocxResult, TokenPackArg, ValuePackArg};
+use crate::lang;
+use regex::Regex;
+use std::ops::Range;
+
+lazy_static! {
+    /// Matches the alternative text (description) of a drawing, where the placeholder picture has its token.
+    static ref DESCR_RE: Regex = Regex::new(r#"<wp:docPr\s[^>]*?\bdescr="([^"]*)""#).unwrap();
+    /// Matches the relationship id of the picture of a drawing.
+    static ref EMBED_RE: Regex = Regex::new(r#"\br:embed="[^"]*""#).unwrap();
+    /// Matches the size of a drawing, as well as the size of its picture.
+    static ref EXTENT_RE: Regex =
+        Regex::new(r#"(<wp:extent|<a:ext)\s+cx="(\d+)"\s+cy="(\d+)""#).unwrap();
+    /// Matches the ids of drawings, that have to be unique in the whole document.
+    static ref DRAWING_ID_RE: Regex = Regex::new(r#"<wp:docPr\s[^>]*?\bid="(\d+)""#).unwrap();
+}
+
+/// Prefix of the image token name, e.g. `{{@PHOTO}}`.
+const IMAGE_PREFIX: char = '@';
+
+/// Size of an image pixel in EMU (English Metric Units used by drawings), for the image resolution of 96 DPI.
+const EMU_PER_PIXEL: u64 = 9525;
+/// Maximal width of the image inserted in place of a token in text (6 inches, in EMU).
+const MAX_IMAGE_WIDTH: u64 = 5486400;
+
+/// Relationship type of an image.
+const IMAGE_RELATIONSHIP: &str =
+    "http://schemas.openxmlformats.org/officeDocument/2006/relationships/image";
+
+/// Image file embedded into the generated document.
+#[derive(Debug, Clone)]
+struct Image {
+    /// name of the document part (e.g. `word/document.xml`), where the image is used.
+    part: String,
+    /// path of the image file.
+    path: String,
+    /// name of the new package part with the image data, e.g. `word/media/docx-filler-image1.png`.
+    name: String,
+    /// id of the relationship between the document part and the image.
+    id: String,
+    /// image file contents.
+    data: Vec<u8>,
+    /// image width in pixels.
+    width: u64,
+    /// image height in pixels.
+    height: u64,
+}
+
+/// Images embedded into a single generated document, to be added as new package parts.
+#[derive(Debug, Default)]
+pub struct Media {
+    /// images used by the document parts (image used by several parts is listed for each of them).
+    images: Vec<Image>,
+    /// the highest id of the drawings of the whole document, new drawings get ids following it.
+    last_drawing_id: u64,
+}
+
+impl Media {
+    /// Creates the media of the generated document without any images.
+    ///
+    /// # Arguments
+    ///
+    /// * `last_drawing_id` - the highest id of the drawings in all the document parts of the template
+    pub fn new(last_drawing_id: u64) -> Media {
+        Media {
+            images: Default::default(),
+            last_drawing_id,
+        }
+    }
+
+    /// Checks whether there are no images embedded into the document.
+    pub fn is_empty(&self) -> bool {
+        self.images.is_empty()
+    }
+
+    /// Loads the image file, and adds it to the images used by the document part.
+    /// Image file added before is not loaded again - the document part uses its relationship
+    /// if it has it already, or gets a new relationship to the same package part otherwise.
+    ///
+    /// # Errors
+    ///
+    /// Can return Docx::Processing if the file cannot be read, or is not a supported image.
+    fn add(&mut self, part: &str, path: &str) -> DocxResult<Image> {
+        if let Some(image) = self.images.iter().find(|image| image.path == path) {
+            let image = Image {
+                part: part.to_string(),
+                ..image.clone()
+            };
+            if !self
+                .images
+                .iter()
+                .any(|added| added.path == path && added.part == part)
+            {
+                self.images.push(image.clone());
+            }
+            return Ok(image);
+        }
+
+        let data = std::fs::read(path).map_err(|_| image_error("docx-filler-image-load", path))?;
+        let (extension, width, height) =
+            image_format(&data).ok_or_else(|| image_error("docx-filler-image-format", path))?;
+        let number = self.files().count() + 1;
+        let image = Image {
+            part: part.to_string(),
+            path: path.to_string(),
+            name: format!("word/media/docx-filler-image{}.{}", number, extension),
+            id: format!("rIdDocxFiller{}", number),
+            data,
+            width,
+            height,
+        };
+        self.images.push(image.clone());
+        Ok(image)
+    }
+
+    /// Lists the image files embedded into the document, each of them once.
+    fn files(&self) -> impl Iterator<Item = &Image> {
+        self.images.iter().enumerate().filter_map(|(i, image)| {
+            match self.images[..i]
+                .iter()
+                .any(|added| added.name == image.name)
+            {
+                true => None,
+                false => Some(image),
+            }
+        })
+    }
+
+    /// Returns the id for a new drawing, unique in the whole document.
+    fn next_drawing_id(&mut self) -> u64 {
+        self.last_drawing_id += 1;
+        self.last_drawing_id
+    }
+
+    /// Lists the new package parts of the document - the image files, and relationships parts
+    /// of the document parts that did not have any relationships in the template.
+    ///
+    /// # Arguments
+    ///
+    /// * `existing` - names of the package parts present in the template
+    pub fn new_parts(&self, existing: &[String]) -> Vec<(String, Vec<u8>)> {
+        let mut parts: Vec<(String, Vec<u8>)> = self
+            .files()
+            .map(|image| (image.name.to_string(), image.data.clone()))
+            .collect();
+        for (rels, xml) in self.new_relationships(existing) {
+            parts.push((rels, xml.into_bytes()));
+        }
+        parts
+    }
+
+    /// Lists the new relationships parts of the document parts that did not have any relationships
+    /// in the template, but have images embedded.
+    ///
+    /// # Arguments
+    ///
+    /// * `existing` - names of the package parts present in the template
+    pub fn new_relationships(&self, existing: &[String]) -> Vec<(String, String)> {
+        let mut parts: Vec<(String, String)> = Default::default();
+        for image in self.images.iter() {
+            let rels = rels_part_of(&image.part);
+            if !existing.contains(&rels) && !parts.iter().any(|(name, _)| *name == rels) {
+                let xml = add_relationships(EMPTY_RELATIONSHIPS, &rels, self);
+                parts.push((rels, xml));
+            }
+        }
+        parts
+    }
+}
+
+/// Contents of the relationships part without any relationships.
+const EMPTY_RELATIONSHIPS: &str = concat!(
+    r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>"#,
+    "\n",
+    r#"<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships"></Relationships>"#
+);
+
+/// Checks whether the token is an image token, e.g. `{{@PHOTO}}`.
+pub fn is_image_token(token: &str, delimiters: &Delimiters) -> bool {
+    delimiters.inner(token).starts_with(IMAGE_PREFIX)
+}
+
+/// Returns the highest id of the drawings in the document part (0 if there are none).
+pub fn max_drawing_id(xml: &str) -> u64 {
+    DRAWING_ID_RE
+        .captures_iter(xml)
+        .filter_map(|cap| cap[1].parse::<u64>().ok())
+        .max()
+        .unwrap_or(0)
+}
+
+/// Lists byte ranges of the drawing descriptions (alternative texts), where the placeholder pictures
+/// have their image tokens.
+pub fn placeholder_ranges(xml: &str) -> Vec<Range<usize>> {
+    DESCR_RE
+        .captures_iter(xml)
+        .filter_map(|cap| cap.get(1))
+        .map(|descr| descr.range())
+        .collect()
+}
+
+/// Embeds the images into the document part, in place of the image tokens (e.g. `{{@PHOTO}}`).
+/// Value of the image token is a path to the image file. Placeholder picture (having the token
+/// as its alternative text) gets its picture replaced, fitting the image into its size.
+/// Token in the text gets replaced by the new picture, of the image size (up to 6 inches wide).
+/// Token with empty value is removed, together with its placeholder picture.
+///
+/// # Arguments
+///
+/// * `xml` - contents of the document part
+/// * `part` - name of the document part, e.g. `word/document.xml`
+/// * `delimiters` - delimiters of the tokens in the template
+/// * `tokens` - vector of tokens to be replaced
+/// * `values` - vector of values to be filled in place of tokens
+/// * `media` - images embedded into the generated document so far
+///
+/// # Errors
+///
+/// Can return Docx::Processing if some of the images cannot be loaded, with details in message.
+pub fn embed_images(
+    xml: &str,
+    part: &str,
+    delimiters: &Delimiters,
+    tokens: TokenPackArg,
+    values: ValuePackArg,
+    media: &mut Media,
+) -> DocxResult<String> {
+    let xml = embed_placeholders(xml, part, delimiters, tokens, values, media)?;
+
+    let mut output = String::with_capacity(xml.len());
+    let mut last = 0;
+    for found in delimiters.token_re().find_iter(&xml) {
+        let token = formats::base_token(found.as_str(), delimiters);
+        let i = match tokens.iter().position(|t| *t == token) {
+            Some(i) if is_image_token(&token, delimiters) => i,
+            _ => continue,
+        };
+        if xml::context_at(&xml, found.start()) != xml::XmlContext::RunText {
+            continue;
+        }
+
+        output.push_str(&xml[last..found.start()]);
+        let path = values[i].text();
+        if !path.trim().is_empty() {
+            let image = media.add(part, path.trim())?;
+            let drawing_id = media.next_drawing_id();
+            output.push_str("</w:t>");
+            output.push_str(&inline_drawing(&image, drawing_id));
+            output.push_str("<w:t>");
+        }
+        last = found.end();
+    }
+    output.push_str(&xml[last..]);
+    Ok(output)
+}
+
+/// Replaces the pictures of placeholder drawings (with image token as their alternative text).
+///
+/// # Errors
+///
+/// Can return Docx::Processing if some of the images cannot be loaded, with details in message.
+fn embed_placeholders(
+    xml: &str,
+    part: &str,
+    delimiters: &Delimiters,
+    tokens: TokenPackArg,
+    values: ValuePackArg,
+    media: &mut Media,
+) -> DocxResult<String> {
+    let mut output = xml.to_string();
+    for drawing in xml::element_ranges(xml, "w:drawing").into_iter().rev() {
+        let fragment = &xml[drawing.clone()];
+        let descr = match DESCR_RE.captures(fragment).and_then(|cap| cap.get(1)) {
+            Some(descr) => descr,
+            None => continue,
+        };
+        let token = formats::base_token(descr.as_str().trim(), delimiters);
+        let i = match tokens.iter().position(|t| *t == token) {
+            Some(i) if is_image_token(&token, delimiters) => i,
+            _ => continue,
+        };
+
+        let path = values[i].text();
+        let replaced = if path.trim().is_empty() {
+            String::new()
+        } else {
+            let image = media.add(part, path.trim())?;
+            let fragment = [&fragment[..descr.start()], &fragment[descr.end()..]].concat();
+            let fragment = EMBED_RE.replace(&fragment, format!(r#"r:embed="{}""#, image.id));
+            fit_extent(&fragment, &image)
+        };
+        output.replace_range(drawing, &replaced);
+    }
+    Ok(output)
+}
+
+/// Changes the size of the placeholder drawing, so that the image fits into it keeping its aspect ratio.
+fn fit_extent(drawing: &str, image: &Image) -> String {
+    let (cx, cy) = match EXTENT_RE.captures(drawing) {
+        Some(cap) => (
+            cap[2].parse::<u64>().unwrap_or(0),
+            cap[3].parse::<u64>().unwrap_or(0),
+        ),
+        None => return drawing.to_string(),
+    };
+    let (width, height) = if cx * image.height > cy * image.width {
+        (cy * image.width / image.height, cy)
+    } else {
+        (cx, cx * image.height / image.width)
+    };
+    EXTENT_RE
+        .replace_all(drawing, |cap: &regex::Captures| {
+            format!(r#"{} cx="{}" cy="{}""#, &cap[1], width, height)
+        })
+        .to_string()
+}
+
+/// Creates the inline drawing element with the image, of the image size (scaled down to the maximal width).
+fn inline_drawing(image: &Image, drawing_id: u64) -> String {
+    let mut width = image.width * EMU_PER_PIXEL;
+    let mut height = image.height * EMU_PER_PIXEL;
+    if width > MAX_IMAGE_WIDTH {
+        height = height * MAX_IMAGE_WIDTH / width;
+        width = MAX_IMAGE_WIDTH;
+    }
+    let name = image.name.rsplit('/').next().unwrap_or_default();
+    format!(
+        concat!(
+            r#"<w:drawing><wp:inline distT="0" distB="0" distL="0" distR="0" "#,
+            r#"xmlns:wp="http://schemas.openxmlformats.org/drawingml/2006/wordprocessingDrawing">"#,
+            r#"<wp:extent cx="{cx}" cy="{cy}"/><wp:docPr id="{id}" name="{name}"/>"#,
+            r#"<a:graphic xmlns:a="http://schemas.openxmlformats.org/drawingml/2006/main">"#,
+            r#"<a:graphicData uri="http://schemas.openxmlformats.org/drawingml/2006/picture">"#,
+            r#"<pic:pic xmlns:pic="http://schemas.openxmlformats.org/drawingml/2006/picture">"#,
+            r#"<pic:nvPicPr><pic:cNvPr id="0" name="{name}"/><pic:cNvPicPr/></pic:nvPicPr>"#,
+            r#"<pic:blipFill><a:blip r:embed="{rid}" "#,
+            r#"xmlns:r="http://schemas.openxmlformats.org/officeDocument/2006/relationships"/>"#,
+            r#"<a:stretch><a:fillRect/></a:stretch></pic:blipFill>"#,
+            r#"<pic:spPr><a:xfrm><a:off x="0" y="0"/><a:ext cx="{cx}" cy="{cy}"/></a:xfrm>"#,
+            r#"<a:prstGeom prst="rect"><a:avLst/></a:prstGeom></pic:spPr></pic:pic>"#,
+            r#"</a:graphicData></a:graphic></wp:inline></w:drawing>"#
+        ),
+        cx = width,
+        cy = height,
+        id = drawing_id,
+        name = name,
+        rid = image.id,
+    )
+}
+
+/// Returns name of the relationships part of the document part
+/// (e.g. `word/_rels/document.xml.rels` for `word/document.xml`).
+pub fn rels_part_of(part: &str) -> String {
+    match part.rsplit_once('/') {
+        Some((dir, file)) => format!("{}/_rels/{}.rels", dir, file),
+        None => format!("_rels/{}.rels", part),
+    }
+}
+
+/// Adds relationships of the images embedded into the document part, to its relationships part.
+///
+/// # Arguments
+///
+/// * `xml` - contents of the relationships part
+/// * `rels_part` - name of the relationships part, e.g. `word/_rels/document.xml.rels`
+/// * `media` - images embedded into the generated document
+pub fn add_relationships(xml: &str, rels_part: &str, media: &Media) -> String {
+    let relationships: String = media
+        .images
+        .iter()
+        .filter(|image| rels_part_of(&image.part) == rels_part)
+        .map(|image| {
+            let target = image.name.strip_prefix("word/").unwrap_or(&image.name);
+            format!(
+                r#"<Relationship Id="{}" Type="{}" Target="{}"/>"#,
+                image.id, IMAGE_RELATIONSHIP, target
+            )
+        })
+        .collect();
+    match xml.rfind("</Relationships>") {
+        Some(end) => [&xml[..end], &relationships, &xml[end..]].concat(),
+        None => xml.to_string(),
+    }
+}
+
+/// Adds content types of the embedded images (by their file extensions), that are not declared yet.
+pub fn add_content_types(xml: &str, media: &Media) -> String {
+    let mut defaults = String::new();
+    for image in media.images.iter() {
+        let extension = image.name.rsplit('.').next().unwrap_or_default();
+        let declaration = format!(r#"Extension="{}""#, extension);
+        if !xml.contains(&declaration) && !defaults.contains(&declaration) {
+            defaults.push_str(&format!(
+                r#"<Default {} ContentType="image/{}"/>"#,
+                declaration,
+                content_subtype(extension)
+            ));
+        }
+    }
+    match xml.rfind("</Types>") {
+        Some(end) => [&xml[..end], &defaults, &xml[end..]].concat(),
+        None => xml.to_string(),
+    }
+}
+
+/// Returns the subtype of the image content type by its file extension, e.g. `jpeg` for `jpg`.
+fn content_subtype(extension: &str) -> &str {
+    match extension {
+        "jpg" => "jpeg",
+        other => other,
+    }
+}
+
+/// Recognizes the image format by the image data, returning the file extension and size of the image in pixels.
+/// PNG, JPEG, GIF and BMP images are supported.
+fn image_format(data: &[u8]) -> Option<(&'static str, u64, u64)> {
+    let be16 = |at: usize| -> Option<u64> {
+        Some(u16::from_be_bytes([*data.get(at)?, *data.get(at + 1)?]) as u64)
+    };
+    let le16 = |at: usize| -> Option<u64> {
+        Some(u16::from_le_bytes([*data.get(at)?, *data.get(at + 1)?]) as u64)
+    };
+    let be32 = |at: usize| -> Option<u64> {
+        Some(u32::from_be_bytes(data.get(at..at + 4)?.try_into().ok()?) as u64)
+    };
+    let le32 = |at: usize| -> Option<u64> {
+        Some(i32::from_le_bytes(data.get(at..at + 4)?.try_into().ok()?).unsigned_abs() as u64)
+    };
+
+    let (extension, width, height) = if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+        ("png", be32(16)?, be32(20)?)
+    } else if data.starts_with(b"GIF8") {
+        ("gif", le16(6)?, le16(8)?)
+    } else if data.starts_with(b"BM") {
+        ("bmp", le32(18)?, le32(22)?)
+    } else if data.starts_with(b"\xFF\xD8") {
+        let mut at = 2;
+        loop {
+            if *data.get(at)? != 0xFF {
+                return None;
+            }
+            let marker = *data.get(at + 1)?;
+            if (0xC0..=0xCF).contains(&marker) && ![0xC4, 0xC8, 0xCC].contains(&marker) {
+                break ("jpg", be16(at + 7)?, be16(at + 5)?);
+            }
+            at += 2 + be16(at + 2)? as usize;
+        }
+    } else {
+        return None;
+    };
+    if width == 0 || height == 0 {
+        return None;
+    }
+    Some((extension, width, height))
+}
+
+/// Creates error with the specified message, for the image file path.
+fn image_error(msg_id: &str, path: &str) -> DocxError {
+    let args: lang::TrArgVec = vec![("path".to_string(), path.to_string())];
+    DocxError::Processing(lang::tr_with_args(msg_id, &args))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::Value;
+    use super::*;
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let mut data = b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR".to_vec();
+        data.extend(width.to_be_bytes());
+        data.extend(height.to_be_bytes());
+        data
+    }
+
+    fn image(width: u64, height: u64) -> Image {
+        Image {
+            part: "word/document.xml".to_string(),
+            path: "a.png".to_string(),
+            name: "word/media/docx-filler-image1.png".to_string(),
+            id: "rIdDocxFiller1".to_string(),
+            data: Default::default(),
+            width,
+            height,
+        }
+    }
+
+    #[test]
+    fn recognizes_image_formats() {
+        assert_eq!(image_format(&png(640, 480)), Some(("png", 640, 480)));
+        assert_eq!(
+            image_format(b"GIF89a\x20\x03\x58\x02"),
+            Some(("gif", 800, 600))
+        );
+        let mut bmp = b"BM".to_vec();
+        bmp.extend([0; 16]);
+        bmp.extend(100i32.to_le_bytes());
+        bmp.extend((-50i32).to_le_bytes());
+        assert_eq!(image_format(&bmp), Some(("bmp", 100, 50)));
+        let jpeg = b"\xFF\xD8\xFF\xE0\x00\x04\x00\x00\xFF\xC0\x00\x11\x08\x01\x2C\x01\x90";
+        assert_eq!(image_format(jpeg), Some(("jpg", 400, 300)));
+        assert_eq!(image_format(&png(0, 480)), None);
+        assert_eq!(image_format(b"\x89PNG\r\n"), None);
+        assert_eq!(image_format(b"not an image"), None);
+    }
+
+    #[test]
+    fn fits_image_into_placeholder() {
+        let drawing = r#"<wp:extent cx="1000" cy="1000"/><a:ext cx="1000" cy="1000"/>"#;
+        assert_eq!(
+            fit_extent(drawing, &image(200, 100)),
+            r#"<wp:extent cx="1000" cy="500"/><a:ext cx="1000" cy="500"/>"#
+        );
+        assert_eq!(
+            fit_extent(drawing, &image(100, 400)),
+            r#"<wp:extent cx="250" cy="1000"/><a:ext cx="250" cy="1000"/>"#
+        );
+        assert_eq!(fit_extent("<w:drawing/>", &image(1, 1)), "<w:drawing/>");
+    }
+
+    #[test]
+    fn embeds_images_in_place_of_tokens() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("photo.png");
+        std::fs::write(&path, png(10, 20)).unwrap();
+        let tokens = vec!["{{@PHOTO}}".to_string(), "{{@LOGO}}".to_string()];
+        let values = vec![
+            Value::Text(path.to_string_lossy().to_string()),
+            Value::Text(String::new()),
+        ];
+        let delimiters = Delimiters::default();
+        let xml = "<w:r><w:t>{{@PHOTO}}{{@LOGO}}</w:t></w:r><w:r><w:t>{{@PHOTO}}</w:t></w:r>";
+        let mut media = Media::new(5);
+        let document = "word/document.xml";
+        let header = "word/header1.xml";
+        let embedded = embed_images(xml, document, &delimiters, &tokens, &values, &mut media);
+        let embedded = embedded.unwrap();
+        let in_header = embed_images(xml, header, &delimiters, &tokens, &values, &mut media);
+
+        assert!(!embedded.contains("{{@"));
+        assert_eq!(embedded.matches("<w:drawing>").count(), 2);
+        assert!(embedded.starts_with("<w:r><w:t></w:t><w:drawing><wp:inline "));
+        assert!(embedded.contains(r#"<wp:extent cx="95250" cy="190500"/><wp:docPr id="6" "#));
+        assert!(embedded.contains(r#"<wp:docPr id="7" "#));
+        assert!(in_header.unwrap().contains(r#"<wp:docPr id="9" "#));
+        assert_eq!(embedded.matches(r#"r:embed="rIdDocxFiller1""#).count(), 2);
+
+        let rels = add_relationships(EMPTY_RELATIONSHIPS, &rels_part_of(document), &media);
+        assert_eq!(rels.matches("<Relationship ").count(), 1);
+        assert!(rels.contains(r#"<Relationship Id="rIdDocxFiller1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/image" Target="media/docx-filler-image1.png"/>"#));
+        let parts = media.new_parts(&[rels_part_of(document)]);
+        let names: Vec<&str> = parts.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(
+            names,
+            [
+                "word/media/docx-filler-image1.png",
+                "word/_rels/header1.xml.rels"
+            ]
+        );
+    }
+
+    #[test]
+    fn replaces_placeholder_picture() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("photo.png");
+        std::fs::write(&path, png(10, 20)).unwrap();
+        let tokens = vec!["{{@PHOTO}}".to_string()];
+        let values = vec![Value::Text(path.to_string_lossy().to_string())];
+        let xml = concat!(
+            r#"<w:r><w:drawing><wp:inline><wp:extent cx="1000" cy="1000"/>"#,
+            r#"<wp:docPr id="1" name="P" descr="{{@PHOTO}}"/><a:blip r:embed="rId4"/>"#,
+            r#"</wp:inline></w:drawing></w:r>"#
+        );
+        let mut media = Media::new(1);
+        let embedded = embed_images(
+            xml,
+            "word/document.xml",
+            &Delimiters::default(),
+            &tokens,
+            &values,
+            &mut media,
+        );
+        assert_eq!(
+            embedded.unwrap(),
+            concat!(
+                r#"<w:r><w:drawing><wp:inline><wp:extent cx="500" cy="1000"/>"#,
+                r#"<wp:docPr id="1" name="P" descr=""/><a:blip r:embed="rIdDocxFiller1"/>"#,
+                r#"</wp:inline></w:drawing></w:r>"#
+            )
+        );
+        let values = vec![Value::Text(String::new())];
+        let removed = embed_images(
+            xml,
+            "word/document.xml",
+            &Delimiters::default(),
+            &tokens,
+            &values,
+            &mut media,
+        );
+        assert_eq!(removed.unwrap(), "<w:r></w:r>");
+    }
+}
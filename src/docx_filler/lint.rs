@@ -0,0 +1,270 @@
+use super::delimiters::Delimiters;
+use super::report::{PartLayout, TokenOccurrence};
+use super::{formats, runs, xml};
+use std::ops::Range;
+
+/// Maximal length (in characters) of the token name, that is not suspicious yet.
+const MAX_NAME_CHARS: usize = 40;
+/// Closing tag of the paragraph, that tokens split by XML markup are looked for up to (in the next paragraph).
+const PARAGRAPH_END: &str = "</w:p>";
+/// Maximal length (in characters) of the text following the unclosed open delimiter, shown in the issue.
+const UNCLOSED_CHARS: usize = 30;
+
+/// Kind of the problem found in the template by the linter.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LintKind {
+    /// open delimiter without the close one, e.g. `{{NAME}` (the following token swallowed by it included).
+    Unclosed,
+    /// close delimiter without the open one, e.g. `{NAME}}`.
+    Unopened,
+    /// token split by other contents of the document, e.g. by a paragraph break or field.
+    SplitByMarkup,
+    /// token containing an XML tag typed as a text, e.g. `{{<b>NAME</b>}}`.
+    MarkupInside,
+    /// token with suspiciously long name (probably a piece of text enclosed in delimiters by mistake).
+    TooLong,
+    /// token different from another token just by whitespace or letter case, e.g. `{{ WRITER}}` and `{{WRITER}}`
+    /// (holds the other token).
+    NearDuplicate(String),
+    /// token declaring other default value than the one declared for the same token before, e.g. `{{TITLE?=Ms.}}`
+    /// after `{{TITLE?=Mr.}}` (holds the default declared first, used for all the occurrences).
+    ConflictingDefault(String),
+}
+
+/// Single problem found in the template by the linter, with details on where it was found.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LintIssue {
+    /// kind of the problem.
+    pub kind: LintKind,
+    /// text of the template with the problem, e.g. `{{NAME}`.
+    pub text: String,
+    /// location of the problem in the template.
+    pub occurrence: TokenOccurrence,
+}
+
+/// Alias for a set of problems found in the template, in the document order (main document first).
+pub type LintIssuePack = Vec<LintIssue>;
+
+/// Looks for malformed and suspicious tokens in the DOCX part - unbalanced delimiters, tokens split
+/// by XML markup or containing XML tags, tokens with too long names, near-duplicates of other tokens,
+/// and tokens with conflicting default values.
+///
+/// # Arguments
+///
+/// * `part` - name of the DOCX part, e.g. `word/document.xml`
+/// * `xml` - contents of the DOCX part
+/// * `ranges` - byte ranges of the part contents, where tokens are to be looked for
+/// * `delimiters` - delimiters of the tokens in the template
+/// * `names` - (base) tokens found in the template so far, to look for the near-duplicates among
+/// * `defaults` - default values declared first for the (base) tokens found in the template so far
+/// * `issues` - problems found in the template so far
+pub fn lint_part(
+    part: &str,
+    xml: &str,
+    ranges: &[Range<usize>],
+    delimiters: &Delimiters,
+    names: &mut Vec<String>,
+    defaults: &mut Vec<(String, String)>,
+    issues: &mut LintIssuePack,
+) {
+    let layout = PartLayout::new(part, xml);
+    let mut issue = |kind: LintKind, text: &str, range: Range<usize>| {
+        let mut occurrence = layout.occurrence(xml, &range);
+        occurrence.context = delimiters.restore_escapes(&occurrence.context);
+        issues.push(LintIssue {
+            kind,
+            text: delimiters.restore_escapes(&xml::unescape(text)),
+            occurrence,
+        });
+    };
+
+    // close delimiters up to this position are known to close the tokens split by markup
+    let mut split_end: usize = 0;
+    for range in ranges {
+        let mut text = xml[range.clone()].to_string();
+        for found in delimiters.literal_re().find_iter(&xml[range.clone()]) {
+            text.replace_range(found.range(), &" ".repeat(found.as_str().len()));
+        }
+        for found in delimiters.token_re().find_iter(&xml[range.clone()]) {
+            let mut token_range = range.start + found.start()..range.start + found.end();
+            let mut token = found.as_str();
+            text.replace_range(found.range(), &" ".repeat(found.as_str().len()));
+
+            // the unclosed token swallows the following one, which is linted on its own then
+            if let Some(pos) = delimiters.inner(token).rfind(delimiters.open()) {
+                let start = delimiters.open().len() + pos;
+                issue(
+                    LintKind::Unclosed,
+                    token[..start].trim_end(),
+                    token_range.clone(),
+                );
+                token = &token[start..];
+                token_range.start += start;
+            }
+            let inner = delimiters.inner(token);
+            if inner.contains("&lt;") || inner.contains("&gt;") {
+                issue(LintKind::MarkupInside, token, token_range);
+                continue;
+            }
+
+            let parts = formats::split_token(token, delimiters);
+            let base = parts.token;
+            let name = delimiters.inner(&base);
+            if name.trim_start().starts_with(['#', '/']) {
+                continue;
+            }
+            if name.chars().count() > MAX_NAME_CHARS {
+                issue(LintKind::TooLong, token, token_range);
+                continue;
+            }
+            if let Some(default) = parts.default {
+                match defaults.iter().find(|(known, _)| *known == base) {
+                    Some((_, first)) if *first != default => issue(
+                        LintKind::ConflictingDefault(first.to_string()),
+                        token,
+                        token_range.clone(),
+                    ),
+                    Some(_) => {}
+                    None => defaults.push((base.clone(), default)),
+                }
+            }
+            if !names.contains(&base) {
+                let similar = names
+                    .iter()
+                    .find(|known| similarity_key(known) == similarity_key(&base));
+                match similar {
+                    Some(similar) => issue(
+                        LintKind::NearDuplicate(similar.to_string()),
+                        token,
+                        token_range,
+                    ),
+                    None => names.push(base),
+                }
+            }
+        }
+
+        for (pos, _) in text.match_indices(delimiters.open()) {
+            let open_at = range.start + pos;
+            let open_range = open_at..open_at + delimiters.open().len();
+            match split_token(xml, open_at, range.end, delimiters) {
+                Some((close_at, split)) => {
+                    split_end = split_end.max(close_at);
+                    issue(LintKind::SplitByMarkup, &split, open_range);
+                }
+                None => {
+                    let unclosed: String = text[pos..].chars().take(UNCLOSED_CHARS).collect();
+                    issue(LintKind::Unclosed, unclosed.trim_end(), open_range);
+                }
+            }
+        }
+        for (pos, close) in text.match_indices(delimiters.close()) {
+            let close_at = range.start + pos;
+            if close_at >= split_end {
+                issue(LintKind::Unopened, close, close_at..close_at + close.len());
+            }
+        }
+    }
+}
+
+/// Looks for the close delimiter of the token split by XML markup (e.g. by a paragraph break), following
+/// the open one in the rest of the paragraph, or in the next paragraph. Returns the position of the close
+/// delimiter, along with the visible text of the token, if there is such delimiter
+/// (and no other open delimiter precedes it).
+fn split_token(
+    xml: &str,
+    open_at: usize,
+    node_end: usize,
+    delimiters: &Delimiters,
+) -> Option<(usize, String)> {
+    let paragraph_end = node_end + xml[node_end..].find(PARAGRAPH_END)? + PARAGRAPH_END.len();
+    let search_end = xml[paragraph_end..]
+        .find(PARAGRAPH_END)
+        .map_or(paragraph_end, |end| paragraph_end + end);
+    let mut text = xml[open_at..node_end].to_string();
+    for node in runs::text_nodes(&xml[node_end..search_end]) {
+        let content = &xml[node_end + node.content.start..node_end + node.content.end];
+        let close = content.find(delimiters.close());
+        let open = content.find(delimiters.open());
+        match (close, open) {
+            (Some(close), open) if !matches!(open, Some(open) if open < close) => {
+                let close_end = close + delimiters.close().len();
+                text.push_str(&content[..close_end]);
+                return Some((node_end + node.content.start + close_end, text));
+            }
+            (_, Some(_)) => return None,
+            _ => text.push_str(content),
+        }
+    }
+    None
+}
+
+/// Returns the key of the token, that is the same for tokens differing just by whitespace or letter case.
+fn similarity_key(token: &str) -> String {
+    token
+        .chars()
+        .filter(|ch| !ch.is_whitespace())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lint(text: &str) -> Vec<(LintKind, String)> {
+        let xml = format!("<w:p><w:r><w:t>{}</w:t></w:r></w:p>", text);
+        let whole = 0..xml.len();
+        let mut issues: LintIssuePack = Default::default();
+        lint_part(
+            "word/document.xml",
+            &xml,
+            std::slice::from_ref(&whole),
+            &Delimiters::default(),
+            &mut Default::default(),
+            &mut Default::default(),
+            &mut issues,
+        );
+        issues
+            .into_iter()
+            .map(|issue| (issue.kind, issue.text))
+            .collect()
+    }
+
+    #[test]
+    fn reports_unbalanced_delimiters() {
+        assert_eq!(
+            lint("{{A} {{B}} C}}"),
+            vec![
+                (LintKind::Unclosed, "{{A}".to_string()),
+                (LintKind::Unopened, "}}".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_near_duplicates() {
+        assert_eq!(
+            lint("{{Writer}} {{WRITER|upper}}"),
+            vec![(
+                LintKind::NearDuplicate("{{Writer}}".to_string()),
+                "{{WRITER|upper}}".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn reports_conflicting_defaults() {
+        assert_eq!(
+            lint("{{T?=Mr.}} {{T}} {{T?=Mr.|upper}} {{T?=Ms.}}"),
+            vec![(
+                LintKind::ConflictingDefault("Mr.".to_string()),
+                "{{T?=Ms.}}".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn keeps_escaped_literals() {
+        assert!(lint(r"\{{code}} {{A}}").is_empty());
+    }
+}
@@ -0,0 +1,344 @@
+use super::delimiters::Delimiters;
+use super::engine::{CompiledXml, Output};
+use super::{
+    blocks, formats, runs, xml, DocxError, DocxResult, TokenPack, TokenPackArg, ValuePackArg,
+};
+use crate::lang;
+use regex::Regex;
+use std::ops::Range;
+
+/// Paragraph put in place of the table removed from the end of a table cell.
+const EMPTY_PARAGRAPH: &str = "<w:p/>";
+
+lazy_static! {
+    /// Matches text (between the delimiters) of opening (`{{#each NAME}}`) and closing (`{{/each}}`) markers
+    /// of the repeated table rows.
+    static ref MARKER_RE: Regex = Regex::new(r"^(?:#each\s+(.*?)\s*|/each)$").unwrap();
+}
+
+/// List tokens of the template (e.g. `{{items}}`), each with its fields (e.g. `{{items.qty}}`),
+/// in order of their first occurrence.
+pub type ListFields = Vec<(String, TokenPack)>;
+
+/// Returns the list token repeated by the loop, if the input token is its opening marker
+/// (e.g. `{{items}}` for `{{#each items}}`).
+pub fn list_token(token: &str, delimiters: &Delimiters) -> Option<String> {
+    let cap = MARKER_RE.captures(delimiters.inner(token))?;
+    cap.get(1).map(|name| delimiters.wrap(name.as_str()))
+}
+
+/// Checks whether the input token is a marker of the repeated rows (opening or closing one).
+pub fn is_marker(token: &str, delimiters: &Delimiters) -> bool {
+    MARKER_RE.is_match(delimiters.inner(token))
+}
+
+/// Returns the list token, that the input (base) token is a field of (e.g. `{{items}}` for `{{items.qty}}`).
+pub fn field_of(token: &str, lists: &ListFields, delimiters: &Delimiters) -> Option<String> {
+    let inner = delimiters.inner(token);
+    lists
+        .iter()
+        .map(|(list, _)| list)
+        .find(|list| {
+            let prefix = format!("{}.", delimiters.inner(list));
+            inner.starts_with(&prefix) && inner.len() > prefix.len()
+        })
+        .cloned()
+}
+
+/// Collects the list tokens and their fields used in the document part.
+///
+/// # Arguments
+///
+/// * `xml` - contents of the document part
+/// * `delimiters` - delimiters of the tokens in the template
+/// * `lists` - list tokens with fields, found so far in the template
+pub fn collect_fields(xml: &str, delimiters: &Delimiters, lists: &mut ListFields) {
+    for node in runs::text_nodes(xml) {
+        for token in delimiters.token_re().find_iter(&xml[node.content]) {
+            if let Some(list) = list_token(token.as_str(), delimiters) {
+                if !lists.iter().any(|(known, _)| *known == list) {
+                    lists.push((list, Default::default()));
+                }
+                continue;
+            }
+            let token = formats::base_token(token.as_str(), delimiters);
+            if let Some(list) = field_of(&token, lists, delimiters) {
+                if let Some((_, fields)) = lists.iter_mut().find(|(known, _)| *known == list) {
+                    if !fields.contains(&token) {
+                        fields.push(token);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Repeats the table rows of each loop (`{{#each items}}` ... `{{/each}}`) once per each item
+/// of the list value. Values of the item are filled in place of the list fields (`{{items.FIELD}}`),
+/// in order of the fields first occurrence in the template. Markers themselves are removed.
+/// Loops of list tokens left out of the tokens to be replaced are kept as they are, markers included.
+///
+/// # Arguments
+///
+/// * `xml` - contents of the document part
+/// * `lists` - list tokens with fields of the template
+/// * `delimiters` - delimiters of the tokens in the template
+/// * `tokens` - vector of tokens to be replaced
+/// * `values` - vector of values to be filled in place of tokens
+///
+/// # Errors
+///
+/// Can return Docx::Processing on improperly placed loops, with details in message.
+pub fn expand_loops(
+    xml: &str,
+    lists: &ListFields,
+    delimiters: &Delimiters,
+    tokens: TokenPackArg,
+    values: ValuePackArg,
+) -> DocxResult<String> {
+    let mut output = xml.to_string();
+    while let Some((open, close)) = first_loop(&output, delimiters)? {
+        let list = list_token(&output[open.clone()], delimiters).unwrap_or_default();
+        let rows = loop_rows(&output, &open, &close)?;
+        let list_index = match tokens.iter().position(|token| *token == list) {
+            Some(i) => i,
+            None => {
+                output = [
+                    &output[..open.start],
+                    &delimiters.hide(&output[open.clone()]),
+                    &output[open.end..close.start],
+                    &delimiters.hide(&output[close.clone()]),
+                    &output[close.end..],
+                ]
+                .concat();
+                continue;
+            }
+        };
+        let row_template = [
+            &output[rows.start..open.start],
+            &output[open.end..close.start],
+            &output[close.end..rows.end],
+        ]
+        .concat();
+
+        let fields = lists
+            .iter()
+            .find(|(known, _)| *known == list)
+            .map(|(_, fields)| fields.clone())
+            .unwrap_or_default();
+        let items = values[list_index].items();
+
+        let mut expanded = String::new();
+        for mut item in items {
+            item.resize(fields.len(), Default::default());
+            let item_tokens = [&fields[..], tokens].concat();
+            let item_values = [&item[..], values].concat();
+            let row =
+                blocks::resolve_conditions(&row_template, delimiters, &item_tokens, &item_values)?;
+            let compiled = CompiledXml::compile(&row, delimiters);
+            expanded.push_str(&compiled.render(&item_tokens, &item_values, Output::Fragment));
+        }
+
+        output = [&output[..rows.start], &expanded, &output[rows.end..]].concat();
+        if expanded.is_empty() {
+            output = remove_empty_table(&output, rows.start);
+        }
+    }
+    Ok(delimiters.reveal(&output))
+}
+
+/// Removes the table enclosing the specified position, if it has no rows left (e.g. after repeating its only row
+/// for the empty list), as a table without rows is not valid. Table removed from the end of a table cell
+/// is replaced by an empty paragraph, as each cell has to end with one.
+fn remove_empty_table(xml: &str, pos: usize) -> String {
+    match xml::enclosing_element(xml, &(pos..pos), "w:tbl") {
+        Some(table) if !xml[table.clone()].contains("<w:tr") => {
+            let replacement = match xml[table.end..].starts_with("</w:tc>") {
+                true => EMPTY_PARAGRAPH,
+                false => "",
+            };
+            [&xml[..table.start], replacement, &xml[table.end..]].concat()
+        }
+        _ => xml.to_string(),
+    }
+}
+
+/// Finds the byte ranges of the first loop markers pair in the document.
+///
+/// # Errors
+///
+/// Can return Docx::Processing if the markers are not paired properly, with details in message.
+fn first_loop(
+    xml: &str,
+    delimiters: &Delimiters,
+) -> DocxResult<Option<(Range<usize>, Range<usize>)>> {
+    let mut markers = delimiters
+        .token_re()
+        .find_iter(xml)
+        .filter(|found| is_marker(found.as_str(), delimiters));
+    let open = match markers.next() {
+        Some(open) => open,
+        None => return Ok(None),
+    };
+    let is_open = |found: &regex::Match| list_token(found.as_str(), delimiters).is_some();
+    match markers.next() {
+        Some(close) if is_open(&open) && !is_open(&close) => {
+            Ok(Some((open.range(), close.range())))
+        }
+        Some(close) if is_open(&open) => {
+            Err(loop_error("docx-filler-loop-unbalanced", close.as_str()))
+        }
+        _ => Err(loop_error("docx-filler-loop-unbalanced", open.as_str())),
+    }
+}
+
+/// Returns byte range of the table rows enclosing the loop markers (from the row with opening marker,
+/// to the row with the closing one).
+///
+/// # Errors
+///
+/// Can return Docx::Processing if the markers are not placed in rows of the same table.
+fn loop_rows(xml: &str, open: &Range<usize>, close: &Range<usize>) -> DocxResult<Range<usize>> {
+    let open_row = xml::enclosing_element(xml, open, "w:tr");
+    let close_row = xml::enclosing_element(xml, close, "w:tr");
+    if let (Some(open_row), Some(close_row)) = (open_row, close_row) {
+        let rows = open_row.start..close_row.end;
+        if open_row.start <= close_row.start && xml::is_balanced(&xml[rows.clone()]) {
+            return Ok(rows);
+        }
+    }
+    Err(loop_error("docx-filler-loop-no-row", &xml[open.clone()]))
+}
+
+/// Creates error with the specified message, for the loop marker.
+fn loop_error(msg_id: &str, marker: &str) -> DocxError {
+    let args: lang::TrArgVec = vec![("marker".to_string(), marker.to_string())];
+    DocxError::Processing(lang::tr_with_args(msg_id, &args))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::docx_filler::Value;
+
+    fn row(cells: &[&str]) -> String {
+        let cells: String = cells
+            .iter()
+            .map(|text| format!("<w:tc><w:p><w:r><w:t>{}</w:t></w:r></w:p></w:tc>", text))
+            .collect();
+        format!("<w:tr>{}</w:tr>", cells)
+    }
+
+    fn expand(xml: &str, list: Value) -> String {
+        let delimiters = Delimiters::default();
+        let mut lists: ListFields = Default::default();
+        collect_fields(xml, &delimiters, &mut lists);
+        let tokens = vec!["{{items}}".to_string(), "{{NAME}}".to_string()];
+        let values = vec![list, Value::from("Ann")];
+        expand_loops(xml, &lists, &delimiters, &tokens, &values).unwrap()
+    }
+
+    fn items(items: &[&[&str]]) -> Value {
+        Value::List(
+            items
+                .iter()
+                .map(|item| item.iter().map(|value| Value::from(*value)).collect())
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn collects_list_fields() {
+        let xml = format!(
+            "<w:tbl>{}</w:tbl>",
+            row(&[
+                "{{#each items}}{{items.qty}}",
+                "{{items.desc|upper}}{{/each}}"
+            ])
+        );
+        let mut lists: ListFields = Default::default();
+        collect_fields(&xml, &Delimiters::default(), &mut lists);
+        assert_eq!(
+            lists,
+            vec![(
+                "{{items}}".to_string(),
+                vec!["{{items.qty}}".to_string(), "{{items.desc}}".to_string()]
+            )]
+        );
+    }
+
+    #[test]
+    fn repeats_row_per_item() {
+        let xml = format!(
+            "<w:tbl>{}{}</w:tbl>",
+            row(&["Qty", "Desc"]),
+            row(&[
+                "{{#each items}}{{items.qty}}",
+                "{{items.desc}} for {{NAME}}{{/each}}"
+            ])
+        );
+        let expanded = expand(&xml, items(&[&["2", "a & b"], &["3", "{{NAME}}"]]));
+        let expected = format!(
+            "<w:tbl>{}{}{}</w:tbl>",
+            row(&["Qty", "Desc"]),
+            row(&["2", "a &amp; b for Ann"]),
+            row(&["3", "\u{FDD2}NAME}} for Ann"])
+        );
+        assert_eq!(expanded, expected);
+    }
+
+    #[test]
+    fn removes_rows_of_empty_list() {
+        let xml = format!(
+            "<w:tbl>{}{}</w:tbl>",
+            row(&["Qty"]),
+            row(&["{{#each items}}{{items.qty}}{{/each}}"])
+        );
+        assert_eq!(
+            expand(&xml, items(&[])),
+            format!("<w:tbl>{}</w:tbl>", row(&["Qty"]))
+        );
+    }
+
+    #[test]
+    fn removes_table_of_empty_list() {
+        let xml = format!(
+            "<w:body><w:tbl><w:tblPr/>{}</w:tbl><w:p/></w:body>",
+            row(&["{{#each items}}{{items.qty}}{{/each}}"])
+        );
+        assert_eq!(expand(&xml, items(&[])), "<w:body><w:p/></w:body>");
+    }
+
+    #[test]
+    fn keeps_paragraph_of_cell_with_removed_table() {
+        let xml = format!(
+            "<w:tc><w:p/><w:tbl>{}</w:tbl></w:tc>",
+            row(&["{{#each items}}{{items.qty}}{{/each}}"])
+        );
+        assert_eq!(expand(&xml, Value::from("")), "<w:tc><w:p/><w:p/></w:tc>");
+    }
+
+    #[test]
+    fn keeps_loop_of_unknown_list() {
+        let xml = format!(
+            "<w:tbl>{}</w:tbl>",
+            row(&["{{#each other}}{{other.x}}{{/each}}"])
+        );
+        assert_eq!(expand(&xml, items(&[&["1"]])), xml);
+    }
+
+    #[test]
+    fn rejects_loop_outside_of_table() {
+        let xml = "<w:p><w:r><w:t>{{#each items}}{{items.qty}}{{/each}}</w:t></w:r></w:p>";
+        let delimiters = Delimiters::default();
+        let tokens = vec!["{{items}}".to_string()];
+        let result = expand_loops(
+            xml,
+            &Default::default(),
+            &delimiters,
+            &tokens,
+            &[items(&[])],
+        );
+        assert!(matches!(result, Err(DocxError::Processing(_))));
+    }
+}
@@ -1,21 +1,139 @@
+mod blocks;
+mod builtins;
+mod combine;
+mod controls;
+mod delimiters;
+mod engine;
+mod fields;
+mod formats;
+mod images;
+mod lint;
+mod loops;
+mod normalize;
+mod properties;
+mod report;
+mod runs;
+mod staging;
+mod types;
 mod validations;
+mod xml;
+
+pub use lint::{LintIssue, LintIssuePack, LintKind};
+pub use normalize::TokenNormalization;
+pub use report::{TokenOccurrence, TokenReport, TokenReportPack};
 
 use crate::lang;
+use rayon::prelude::*;
 use regex::Regex;
 use std::io::{Read, Write};
+use std::ops::Range;
 use std::path::Path;
-use std::{collections::HashMap, fs::File, path::PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::{fs::File, path::PathBuf};
 
 /// Alias for a set of tokens (placeholders).
 pub type TokenPack = Vec<String>;
 pub type TokenPackArg<'a> = &'a [String];
 
+/// Alias for a set of default values of tokens (`None` for the tokens without default value).
+pub type DefaultPack = Vec<Option<String>>;
+pub type DefaultPackArg<'a> = &'a [Option<String>];
+
+/// Alias for a set of types of tokens (`None` for the tokens without declared type).
+pub type TypePack = Vec<Option<types::TokenType>>;
+pub type TypePackArg<'a> = &'a [Option<types::TokenType>];
+
+/// Alias for a set of document properties set explicitly for the generated document, as name & value pairs
+/// (e.g. `title` and `Contract of {{NAME}}`).
+pub type PropertyPack = Vec<(String, String)>;
+pub type PropertyPackArg<'a> = &'a [(String, String)];
+
+/// Token found in the template, along with the names of DOCX parts (document, header, ...) it is used in.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TemplateToken {
+    /// the token text itself, e.g. `{{NAME}}`.
+    pub token: String,
+    /// names of the DOCX parts containing the token, e.g. `word/header1.xml`.
+    pub parts: Vec<String>,
+    /// fields of the list token repeated in table rows (e.g. `{{items.qty}}` for `{{items}}`),
+    /// empty for plain tokens.
+    pub fields: TokenPack,
+    /// default value declared in the template (e.g. `guest` for `{{NAME?=guest}}`),
+    /// used when the value of the token is left empty.
+    pub default: Option<String>,
+    /// type of the value declared in the template (e.g. `int` for `{{AGE:int}}`),
+    /// values not matching it are rejected.
+    pub kind: Option<types::TokenType>,
+}
+
+/// Alias for a set of template tokens, with details on where they were found.
+pub type TemplateTokenPack = Vec<TemplateToken>;
+
+/// Single value to be filled in place of a token.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    /// plain text value.
+    Text(String),
+    /// list of items for the repeated table rows, each item with values of the list fields.
+    List(Vec<ValuePack>),
+}
+
+impl Default for Value {
+    fn default() -> Self {
+        Value::Text(Default::default())
+    }
+}
+
+impl From<&str> for Value {
+    fn from(text: &str) -> Self {
+        Value::Text(text.to_string())
+    }
+}
+
+impl Value {
+    /// Returns the text to be filled in place of a plain token.
+    /// List is written out with its item values separated by commas, and items separated by semicolons.
+    pub fn text(&self) -> String {
+        match self {
+            Value::Text(text) => text.to_string(),
+            Value::List(items) => items
+                .iter()
+                .map(|item| item.iter().map(Value::text).collect::<Vec<_>>().join(", "))
+                .collect::<Vec<_>>()
+                .join("; "),
+        }
+    }
+
+    /// Returns the items of the list value. Plain text value is read as a list written without the brackets.
+    pub fn items(&self) -> Vec<ValuePack> {
+        match self {
+            Value::List(items) => items.clone(),
+            Value::Text(text) => string_to_list(text),
+        }
+    }
+}
+
 /// Alias for a set of values to be filled into placeholders.
-pub type ValuePack = Vec<String>;
-pub type ValuePackArg<'a> = &'a [String];
+pub type ValuePack = Vec<Value>;
+pub type ValuePackArg<'a> = &'a [Value];
+
+/// Separator of the items in the list value, e.g. `[2, apples | 3, pears]`.
+const LIST_ITEM_SEPARATOR: char = '|';
+/// Separator of the values of each item in the list value, e.g. `[2, apples | 3, pears]`.
+const LIST_VALUE_SEPARATOR: char = ',';
 
 type DocxResult<T> = Result<T, DocxError>;
 
+/// Format of each line of values in the batch input text.
+#[derive(Debug, Clone, Default)]
+struct LineFormat {
+    /// string separating the values on each line.
+    separator: String,
+    /// whether each of the values (in the order of the tokens) is a list of the repeated table rows,
+    /// that can be enclosed in square brackets (see `string_to_values`).
+    lists: Vec<bool>,
+}
+
 /// Error returned on failure of some of the docx-filler methods.
 /// String representation should give details on what specifically went wrong.
 #[derive(Debug, thiserror::Error)]
@@ -24,27 +142,178 @@ pub enum DocxError {
     Io(#[from] std::io::Error),
     #[error("Zip error")]
     Zip(#[from] zip::result::ZipError),
+    #[error("Encoding error")]
+    Encoding(#[from] std::string::FromUtf8Error),
     #[error("Validation error: {0}")]
     Validation(String),
     #[error("Processing error: {0}")]
     Processing(String),
 }
 
-type FileMap = HashMap<String, String>;
+/// Contents of a single DOCX package part (file inside the DOCX zip).
+#[derive(Debug)]
+enum PartData {
+    /// raw bytes of a part copied into the generated documents as is (images, fonts, styles, ...).
+    Binary(Vec<u8>),
+    /// decoded XML of a part with text of the document, where tokens get filled.
+    Text(String),
+}
+
+/// DOCX package part, along with the details of its zip entry in the template.
+#[derive(Debug)]
+struct PackagePart {
+    /// name (path) of the part within the package.
+    name: String,
+    /// contents of the part.
+    data: PartData,
+    /// compression method used for the part in the template.
+    compression: zip::CompressionMethod,
+    /// last modification time of the part in the template.
+    last_modified: zip::DateTime,
+    /// unix permissions of the part in the template, if there are any.
+    unix_mode: Option<u32>,
+}
+
+impl PackagePart {
+    /// Returns options for writing the part into the package the same way it was stored in the template.
+    fn zip_options(&self) -> zip::write::FileOptions {
+        let compression = match self.compression {
+            zip::CompressionMethod::Stored => zip::CompressionMethod::Stored,
+            _ => zip::CompressionMethod::Deflated,
+        };
+        let options = zip::write::FileOptions::default()
+            .compression_method(compression)
+            .last_modified_time(self.last_modified);
+        match self.unix_mode {
+            Some(mode) => options.unix_permissions(mode),
+            None => options,
+        }
+    }
+}
+
+/// Ordered list of the package parts, as they are to be written in the generated documents.
+type PartList = Vec<PackagePart>;
+
+/// Name of the package part that has to be the first one in the DOCX zip.
+const CONTENT_TYPES_PART: &str = "[Content_Types].xml";
+
+/// Options of loading the DOCX template.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TemplateOptions {
+    /// text opening each token, e.g. `{{`.
+    pub open_delimiter: String,
+    /// text closing each token, e.g. `}}`.
+    pub close_delimiter: String,
+    /// whether the content controls used as tokens are removed from the generated documents,
+    /// keeping just the filled in values.
+    pub remove_controls: bool,
+    /// policy of identifying the tokens - which variants of the token name are the same token.
+    pub normalization: TokenNormalization,
+}
+
+impl Default for TemplateOptions {
+    fn default() -> Self {
+        TemplateOptions {
+            open_delimiter: delimiters::DEFAULT_OPEN.to_string(),
+            close_delimiter: delimiters::DEFAULT_CLOSE.to_string(),
+            remove_controls: false,
+            normalization: Default::default(),
+        }
+    }
+}
+
+/// Mode of filling the tokens of the template.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum FillMode {
+    /// all the tokens are filled, the empty values (and default values) included.
+    #[default]
+    Complete,
+    /// only the tokens with non-empty values are filled, the rest is kept in the generated document as it is
+    /// (along with the built-in tokens) - making a narrower template, that can be filled again later.
+    Partial,
+}
+
+/// Output of the batch generation of documents.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum BatchOutput {
+    /// separate document for each line of values, named by the output file pattern.
+    #[default]
+    Separate,
+    /// single document combining the documents of all the lines, each of them starting on a new page
+    /// (named by the output file pattern, filled with values of the first line).
+    Combined,
+}
+
+/// Mode of writing the separate documents of the batch generation.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum BatchMode {
+    /// each document is moved into place as soon as it is generated - documents generated before a failure
+    /// are kept.
+    #[default]
+    Progressive,
+    /// documents are moved into place only when all of them are generated - no document is kept on a failure.
+    Transactional,
+}
+
+lazy_static! {
+    /// Matches names of DOCX parts that carry the text of the document, and thus can contain tokens.
+    /// Relationships of such parts are included, so that tokens can be used in hyperlink addresses,
+    /// as well as the core and custom document properties (title, author, ...).
+    static ref TEXT_PART_RE: Regex =
+        Regex::new(r"^(?:word/(_rels/)?(document|header\d*|footer\d*|footnotes|endnotes|comments)\.xml(\.rels)?|docProps/(core|custom)\.xml)$").unwrap();
+}
 
 /// Main DOCX filler / document generator.
 ///
 /// Loads the contents of DOCX template file into memory (beware huge files).
+/// Loaded template is never changed by generating the documents, so it can be shared between threads.
 #[derive(Debug)]
 pub struct DocxTemplate {
     /// input path of the DOCX template loaded by this struct.
     input_path: PathBuf,
 
-    /// filename/path of the DOCX contents (actual text of the DOCX document).
-    target_xml: String,
+    /// filenames/paths of the DOCX parts with the actual text of the DOCX document (main document first).
+    target_parts: Vec<String>,
+
+    /// list tokens of the template repeated in table rows, with their fields.
+    list_fields: loops::ListFields,
+
+    /// delimiters of the tokens in the template.
+    delimiters: delimiters::Delimiters,
 
-    /// in-memory storage of all the DOCX contents/meta-data.
-    file_data: FileMap,
+    /// token names of the template, normalized by the policy of identifying the tokens.
+    token_names: normalize::TokenNames,
+
+    /// default values declared for the tokens in the template (the first declaration of each token counts).
+    token_defaults: Vec<(String, String)>,
+
+    /// types of values declared for the tokens in the template (the first declaration of each token counts).
+    token_types: Vec<(String, types::TokenType)>,
+
+    /// built-in tokens used in the template, e.g. `{{#index}}`.
+    builtin_tokens: TokenPack,
+
+    /// mode of filling the tokens of the generated documents.
+    fill_mode: FillMode,
+
+    /// output of the batch generation - separate documents, or a single combined one.
+    batch_output: BatchOutput,
+
+    /// mode of writing the separate documents of the batch generation - progressive, or transactional one.
+    batch_mode: BatchMode,
+
+    /// number of the separate documents of the batch generated in parallel (0 for one per each CPU core).
+    workers: usize,
+
+    /// in-memory storage of all the DOCX contents/meta-data, in the order of the template zip entries.
+    file_data: PartList,
+
+    /// document text parts compiled when the template is loaded, ready to be filled repeatedly
+    /// (parts with loops, conditional blocks or images excepted, as their structure depends on the values).
+    compiled_parts: Vec<(String, engine::CompiledXml)>,
+
+    /// the highest id of the drawings in all the document text parts of the template.
+    last_drawing_id: u64,
 }
 
 #[allow(dead_code)] // TODO - seriously something's wrong with dead code reports!
@@ -58,9 +327,36 @@ impl DocxTemplate {
     /// # Errors
     ///
     /// Can return error if I/O problems are encountered during opening of the DOCX file.
-    /// ZIP related errors can also be raised when reading the DOCX contents into memory.
+    /// ZIP related errors can also be raised when reading the DOCX contents into memory,
+    /// as well as encoding errors if some of the document text parts is not a valid UTF-8.
     pub fn open(input: &Path) -> DocxResult<DocxTemplate> {
-        let mut file_map: FileMap = Default::default();
+        DocxTemplate::open_with_options(input, &Default::default())
+    }
+
+    /// Creates the new generator with the specified options (e.g. token delimiters),
+    /// loading the whole input DOCX file into memory.
+    /// Open delimiter preceded by backslash (e.g. `\{{`) is a literal text, not a start of a token.
+    /// Content controls with tag or alias (e.g. `CLIENT`) and mail merge fields (e.g. `MERGEFIELD CLIENT`)
+    /// are used as tokens too (e.g. `{{CLIENT}}`).
+    /// Variants of the token names (e.g. `{{ WRITER}}`, or `{{writer}}` when ignoring the letter case) are replaced
+    /// by the first occurrence of the name in the template (e.g. `{{WRITER}}`), so they become one token.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - path to the file to be loaded (absolute/relative to the running app)
+    /// * `options` - options of the template loading
+    ///
+    /// # Errors
+    ///
+    /// Can return error if I/O problems are encountered during opening of the DOCX file.
+    /// ZIP related errors can also be raised when reading the DOCX contents into memory,
+    /// as well as encoding errors if some of the document text parts is not a valid UTF-8.
+    /// Validation error is returned for invalid options.
+    pub fn open_with_options(input: &Path, options: &TemplateOptions) -> DocxResult<DocxTemplate> {
+        let mut file_list: PartList = Default::default();
+        let mut target_parts: Vec<String> = Default::default();
+        let delimiters =
+            delimiters::Delimiters::new(&options.open_delimiter, &options.close_delimiter)?;
 
         let zip_file = File::open(input)?;
         let mut zip = zip::ZipArchive::new(zip_file)?;
@@ -68,62 +364,410 @@ impl DocxTemplate {
             let mut entry = zip.by_index(i)?;
 
             let key = String::from(entry.name());
-            let mut file_buffer = String::new();
-            entry.read_to_string(&mut file_buffer)?;
+            let mut file_buffer: Vec<u8> = Vec::with_capacity(entry.size() as usize);
+            entry.read_to_end(&mut file_buffer)?;
 
-            file_map.insert(key, file_buffer);
+            let data = if TEXT_PART_RE.is_match(&key) {
+                let contents = String::from_utf8(file_buffer)?;
+                target_parts.push(key.clone());
+                let contents =
+                    controls::controls_to_tokens(&contents, &delimiters, options.remove_controls);
+                let contents = fields::merge_fields_to_tokens(&contents, &delimiters);
+                let contents = runs::merge_split_tokens(&contents, delimiters.merge_re());
+                PartData::Text(delimiters.escape_literals(&contents))
+            } else {
+                PartData::Binary(file_buffer)
+            };
+
+            file_list.push(PackagePart {
+                name: key,
+                data,
+                compression: entry.compression(),
+                last_modified: entry.last_modified(),
+                unix_mode: entry.unix_mode(),
+            });
         }
+        file_list.sort_by_key(|part| part.name != CONTENT_TYPES_PART);
+        target_parts.sort_by_key(|name| {
+            (
+                name != "word/document.xml",
+                name.ends_with(".rels"),
+                name.to_string(),
+            )
+        });
 
-        Ok(DocxTemplate {
+        let mut template = DocxTemplate {
             input_path: PathBuf::from(input),
-            target_xml: String::from("word/document.xml"),
-            file_data: file_map,
-        })
+            target_parts,
+            list_fields: Default::default(),
+            delimiters,
+            token_names: normalize::TokenNames::new(options.normalization),
+            token_defaults: Default::default(),
+            token_types: Default::default(),
+            builtin_tokens: Default::default(),
+            fill_mode: Default::default(),
+            batch_output: Default::default(),
+            batch_mode: Default::default(),
+            workers: 0,
+            file_data: file_list,
+            compiled_parts: Default::default(),
+            last_drawing_id: 0,
+        };
+        for part in template.target_parts.clone() {
+            if let Some(contents) = template.part_contents(&part) {
+                let ranges = searchable_ranges(&part, &contents);
+                let normalized =
+                    template
+                        .token_names
+                        .normalize_xml(&contents, &ranges, &template.delimiters);
+                template.set_part_contents(&part, normalized);
+            }
+        }
+        for part in template.target_parts.iter() {
+            if let Some(contents) = template.part_contents(part) {
+                loops::collect_fields(&contents, &template.delimiters, &mut template.list_fields);
+                for range in searchable_ranges(part, &contents) {
+                    builtins::collect_builtins(
+                        &contents[range.clone()],
+                        &template.delimiters,
+                        &mut template.builtin_tokens,
+                    );
+                    let token_re = template.delimiters.token_re();
+                    for token in token_re.find_iter(&contents[range]) {
+                        let parts = formats::split_token(token.as_str(), &template.delimiters);
+                        if let Some(kind) = parts.kind {
+                            if template.kind_of(&parts.token).is_none() {
+                                template.token_types.push((parts.token.clone(), kind));
+                            }
+                        }
+                        if let Some(default) = parts.default {
+                            if template.default_of(&parts.token).is_none() {
+                                template.token_defaults.push((parts.token, default));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        for part in template.target_parts.iter() {
+            if let Some(contents) = template.part_contents(part) {
+                let last_drawing_id = images::max_drawing_id(&contents);
+                template.last_drawing_id = template.last_drawing_id.max(last_drawing_id);
+                let compiled = engine::CompiledXml::compile(&contents, &template.delimiters);
+                if !template.is_structured(&contents, &compiled) {
+                    template.compiled_parts.push((part.to_string(), compiled));
+                }
+            }
+        }
+        Ok(template)
     }
 
-    /// Get the tokens identified in the DOCX template.
+    /// Get the tokens identified in the DOCX template, in order of their first occurrence.
+    /// Main document is searched first, followed by headers, footers, footnotes, endnotes and comments.
+    /// Formatted variants of a token (e.g. `{{NAME|upper}}`) are reported as its base token (`{{NAME}}`).
+    /// Built-in tokens (e.g. `{{#index}}`) are not reported, as their values are computed by the filler.
     ///
     /// # Errors
     ///
     /// Can return errors if no DOCX is loaded when attempting this,
     /// or when parsing of tokens fail.
-    pub fn template_tokens(&self) -> DocxResult<TokenPack> {
-        let document = self
-            .document_contents()
-            .ok_or_else(|| DocxError::Processing(lang::tr("ui-docx-no-template")))?;
-
-        let re = match Regex::new(r"\{\{.*?\}\}") {
-            Ok(re) => re,
-            Err(err) => {
-                return Err(DocxError::Processing(err.to_string()));
-            }
-        };
+    pub fn template_tokens(&self) -> DocxResult<TemplateTokenPack> {
+        if self.target_parts.is_empty() {
+            return Err(DocxError::Processing(lang::tr("ui-docx-no-template")));
+        }
+
+        let delims = &self.delimiters;
+        let mut tokens: TemplateTokenPack = Default::default();
+        for part in self.target_parts.iter() {
+            let contents = self
+                .part_contents(part)
+                .ok_or_else(|| DocxError::Processing(lang::tr("docx-filler-fail-load")))?;
 
-        let caps = re.captures_iter(&document);
+            blocks::validate_blocks(&contents, delims)?;
 
-        let mut tokens: TokenPack = Default::default();
-        for cap in caps {
-            if let Some(token) = cap.get(0) {
-                let token_str = token.as_str().to_string();
-                if !tokens.contains(&token_str) {
-                    tokens.push(token_str);
+            for range in searchable_ranges(part, &contents) {
+                for token in delims.token_re().find_iter(&contents[range]) {
+                    let token_str = match self.reported_token(token.as_str())? {
+                        Some(token_str) => token_str,
+                        None => continue,
+                    };
+                    match tokens.iter_mut().find(|t| t.token == token_str) {
+                        Some(known) => {
+                            if !known.parts.contains(part) {
+                                known.parts.push(part.to_string());
+                            }
+                        }
+                        None => tokens.push(TemplateToken {
+                            fields: self.fields_of(&token_str),
+                            default: self.default_of(&token_str),
+                            kind: self.kind_of(&token_str),
+                            token: token_str,
+                            parts: vec![part.to_string()],
+                        }),
+                    }
                 }
             }
         }
 
-        validations::validate_tokens(&tokens)?;
+        validations::validate_tokens(&token_names(&tokens))?;
         Ok(tokens)
     }
 
-    /// Get the whole textual content of the DOCX template document.
-    fn document_contents(&self) -> Option<String> {
-        let document = self.file_data.get(&self.target_xml);
-        document.map(|content| content.to_string())
+    /// Get the report on the tokens identified in the DOCX template - each token along with all of its occurrences,
+    /// located by the DOCX part, paragraph and placement (table, header or text box), with a snippet of the text
+    /// around. Tokens are reported the same way as by `template_tokens`, in the same order.
+    ///
+    /// # Errors
+    ///
+    /// Can return errors if no DOCX is loaded when attempting this,
+    /// or when parsing of tokens fail.
+    pub fn token_report(&self) -> DocxResult<TokenReportPack> {
+        let mut reports: TokenReportPack = token_names(&self.template_tokens()?)
+            .into_iter()
+            .map(|token| TokenReport {
+                token,
+                occurrences: Default::default(),
+            })
+            .collect();
+
+        for part in self.target_parts.iter() {
+            let contents = self
+                .part_contents(part)
+                .ok_or_else(|| DocxError::Processing(lang::tr("docx-filler-fail-load")))?;
+            let layout = report::PartLayout::new(part, &contents);
+
+            for range in searchable_ranges(part, &contents) {
+                for found in self
+                    .delimiters
+                    .token_re()
+                    .find_iter(&contents[range.clone()])
+                {
+                    let token = match self.reported_token(found.as_str())? {
+                        Some(token) => token,
+                        None => continue,
+                    };
+                    if let Some(report) = reports.iter_mut().find(|report| report.token == token) {
+                        let found_range = range.start + found.start()..range.start + found.end();
+                        let mut occurrence = layout.occurrence(&contents, &found_range);
+                        occurrence.context = self.delimiters.restore_escapes(&occurrence.context);
+                        report.occurrences.push(occurrence);
+                    }
+                }
+            }
+        }
+        Ok(reports)
+    }
+
+    /// Looks for malformed and suspicious tokens in the template - unbalanced delimiters (e.g. `{{NAME}`),
+    /// tokens split by other contents or containing XML tags, tokens with suspiciously long names,
+    /// tokens differing from other ones just by whitespace or letter case (e.g. `{{ WRITER}}` and `{{WRITER}}`),
+    /// and tokens declaring different default values (e.g. `{{TITLE?=Mr.}}` and `{{TITLE?=Ms.}}`).
+    /// Problems are located the same way as the tokens by `token_report`.
+    pub fn lint(&self) -> LintIssuePack {
+        let mut issues: LintIssuePack = Default::default();
+        let mut names: TokenPack = Default::default();
+        let mut defaults: Vec<(String, String)> = Default::default();
+        for part in self.target_parts.iter() {
+            if let Some(contents) = self.part_contents(part) {
+                let ranges = searchable_ranges(part, &contents);
+                lint::lint_part(
+                    part,
+                    &contents,
+                    &ranges,
+                    &self.delimiters,
+                    &mut names,
+                    &mut defaults,
+                    &mut issues,
+                );
+            }
+        }
+        issues
+    }
+
+    /// Get the token to be reported for the text found in the template (e.g. `{{VIP}}` for `{{#if VIP}}`,
+    /// or `{{NAME}}` for `{{NAME|upper}}`), if there is any. Markers, built-in tokens and list fields
+    /// are not reported, as they do not need values of their own.
+    ///
+    /// # Errors
+    ///
+    /// Can return Docx::Validation for the token with unknown formatter, with details in message.
+    fn reported_token(&self, found: &str) -> DocxResult<Option<String>> {
+        let delims = &self.delimiters;
+        if let Some(condition) = blocks::condition_token(found, delims) {
+            return Ok(Some(condition));
+        }
+        if let Some(list) = loops::list_token(found, delims) {
+            return Ok(Some(list));
+        }
+        if blocks::is_marker(found, delims) || loops::is_marker(found, delims) {
+            return Ok(None);
+        }
+        formats::validate_token(found, delims)?;
+        let base = formats::base_token(found, delims);
+        if builtins::is_builtin(&base, delims)
+            || loops::field_of(&base, &self.list_fields, delims).is_some()
+        {
+            return Ok(None);
+        }
+        Ok(Some(base))
+    }
+
+    /// Get the fields of the list token, or empty set for the plain token.
+    fn fields_of(&self, token: &str) -> TokenPack {
+        self.list_fields
+            .iter()
+            .find(|(list, _)| list == token)
+            .map(|(_, fields)| fields.clone())
+            .unwrap_or_default()
+    }
+
+    /// Get the format of each line of the batch input - values of the list tokens (with fields used
+    /// in the repeated table rows) can be enclosed in square brackets, with separators inside of them.
+    fn line_format(&self, tokens: TokenPackArg, separator: &str) -> LineFormat {
+        LineFormat {
+            separator: separator.to_string(),
+            lists: tokens
+                .iter()
+                .map(|token| !self.fields_of(token).is_empty())
+                .collect(),
+        }
+    }
+
+    /// Get the default value declared for the token in the template, if there is any.
+    fn default_of(&self, token: &str) -> Option<String> {
+        self.token_defaults
+            .iter()
+            .find(|(known, _)| known == token)
+            .map(|(_, default)| default.to_string())
+    }
+
+    /// Get the default values declared for the tokens in the template, in the order of the tokens.
+    /// In partial fill mode, every token is taken as having an empty default value - so any of the values
+    /// can be left empty or omitted, and the tokens are kept for the later fill with their defaults intact.
+    fn defaults_of(&self, tokens: TokenPackArg) -> DefaultPack {
+        match self.fill_mode {
+            FillMode::Complete => tokens.iter().map(|token| self.default_of(token)).collect(),
+            FillMode::Partial => vec![Some(String::new()); tokens.len()],
+        }
+    }
+
+    /// Sets the mode of filling the tokens of the generated documents (complete one by default).
+    pub fn set_fill_mode(&mut self, mode: FillMode) {
+        self.fill_mode = mode;
+    }
+
+    /// Sets the output of the batch generation - separate documents (by default), or a single combined one.
+    pub fn set_batch_output(&mut self, output: BatchOutput) {
+        self.batch_output = output;
+    }
+
+    /// Sets the mode of writing the separate documents of the batch generation - progressive (by default),
+    /// or transactional one, keeping no document on a failure.
+    pub fn set_batch_mode(&mut self, mode: BatchMode) {
+        self.batch_mode = mode;
+    }
+
+    /// Sets the number of the separate documents of the batch generated in parallel - 0 (by default) for one
+    /// per each CPU core, 1 to generate them one after another.
+    pub fn set_workers(&mut self, workers: usize) {
+        self.workers = workers;
+    }
+
+    /// Get the tokens and values to be filled in the generated document, depending on the fill mode.
+    /// In partial fill mode, tokens with empty values and the built-in tokens are left out, to be kept as they are.
+    fn filled_tokens(&self, tokens: TokenPackArg, values: ValuePackArg) -> (TokenPack, ValuePack) {
+        tokens
+            .iter()
+            .zip(values)
+            .filter(|(token, value)| match self.fill_mode {
+                FillMode::Complete => true,
+                FillMode::Partial => {
+                    let blank = matches!(value, Value::Text(text) if text.trim().is_empty());
+                    !blank && !builtins::is_builtin(token, &self.delimiters)
+                }
+            })
+            .map(|(token, value)| (token.to_string(), value.clone()))
+            .unzip()
+    }
+
+    /// Get the type of value declared for the token in the template, if there is any.
+    fn kind_of(&self, token: &str) -> Option<types::TokenType> {
+        self.token_types
+            .iter()
+            .find(|(known, _)| known == token)
+            .map(|(_, kind)| *kind)
+    }
+
+    /// Get the types of values declared for the tokens in the template, in the order of the tokens.
+    fn types_of(&self, tokens: TokenPackArg) -> TypePack {
+        tokens.iter().map(|token| self.kind_of(token)).collect()
+    }
+
+    /// Get the built-in tokens used in the template, the output pattern and the document properties set explicitly,
+    /// ready to compute their values.
+    fn builtins(&self, output_pattern: &str, properties: PropertyPackArg) -> builtins::Builtins {
+        let mut tokens = self.builtin_tokens.clone();
+        builtins::collect_builtins(output_pattern, &self.delimiters, &mut tokens);
+        for (_, value) in properties {
+            builtins::collect_builtins(value, &self.delimiters, &mut tokens);
+        }
+        builtins::Builtins::new(tokens, &self.input_path, &self.delimiters)
+    }
+
+    /// Get the whole textual content of the specified DOCX part (for document text parts only).
+    fn part_contents(&self, part: &str) -> Option<String> {
+        let part = self.file_data.iter().find(|p| p.name == part)?;
+        match &part.data {
+            PartData::Text(contents) => Some(contents.to_string()),
+            PartData::Binary(_) => None,
+        }
+    }
+
+    /// Replaces the whole textual content of the specified DOCX part (for document text parts only).
+    fn set_part_contents(&mut self, part: &str, contents: String) {
+        if let Some(part) = self.file_data.iter_mut().find(|p| p.name == part) {
+            if let PartData::Text(_) = part.data {
+                part.data = PartData::Text(contents);
+            }
+        }
+    }
+
+    /// Get the tokens (e.g. `{{ writer}}`) with their names replaced by the variants found in the template
+    /// (e.g. `{{WRITER}}`), according to the policy of identifying the tokens.
+    fn normalized_tokens(&self, tokens: TokenPackArg) -> TokenPack {
+        tokens
+            .iter()
+            .map(|token| self.token_names.normalize(token, &self.delimiters))
+            .collect()
+    }
+
+    /// Checks whether the structure of the document part depends on the values - whether it contains
+    /// loops, conditional blocks or images (image tokens or placeholder pictures).
+    fn is_structured(&self, contents: &str, compiled: &engine::CompiledXml) -> bool {
+        let delims = &self.delimiters;
+        !images::placeholder_ranges(contents).is_empty()
+            || compiled.has_token(|token| {
+                loops::is_marker(token, delims)
+                    || blocks::is_marker(token, delims)
+                    || images::is_image_token(&formats::base_token(token, delims), delims)
+            })
+    }
+
+    /// Get the contents of the specified DOCX part not holding the document text (e.g. numbering definitions).
+    fn binary_contents(&self, part: &str) -> Option<String> {
+        let part = self.file_data.iter().find(|p| p.name == part)?;
+        match &part.data {
+            PartData::Binary(data) => Some(String::from_utf8_lossy(data).to_string()),
+            PartData::Text(_) => None,
+        }
     }
 
     /// Generates a single DOCX file from the loaded template.
     /// Replaces all the tokens/placeholders with the corresponding input values.
+    /// Values of the tokens with default value declared in the template can be left empty, or omitted at the end.
     /// This method can be used repeatedly to generate multiple output files with various input tokens/values.
+    /// Tokens (including the ones of the output pattern) can be given in any variant identified as the same token
+    /// by the template options (e.g. `{{ WRITER}}` for `{{WRITER}}`).
     ///
     /// # Arguments
     ///
@@ -140,62 +784,289 @@ impl DocxTemplate {
         values: ValuePackArg,
         output_pattern: &str,
     ) -> DocxResult<()> {
-        validations::validate_single(tokens, values, output_pattern)?;
-        self.data_to_docx(tokens, values, output_pattern)?;
-        Ok(())
+        self.build_docx_with_properties(tokens, values, &[], output_pattern)
     }
 
-    /// Common executive method for processing one docx file generation form the loaded template.
+    /// Generates a single DOCX file from the loaded template, setting its document properties explicitly.
+    /// Core properties (`title`, `subject`, `author`, `keywords`, `description`, `category`, `status`) are set
+    /// always, custom properties have to be defined in the template. Property values can contain tokens too.
+    /// See `build_docx` for details.
     ///
     /// # Arguments
     ///
     /// * `tokens` - vector of tokens to be replaced
     /// * `values` - vector of values to be filled in place of tokens
+    /// * `properties` - document properties to be set, as name & value pairs (e.g. `title` and `Contract`)
     /// * `output_pattern` - output file pattern (explicit string or pattern contains tokens)
     ///
     /// # Errors
     ///
     /// Can return errors on inconsistent input data or other internal problems (see error message for details).
-    fn data_to_docx(
+    pub fn build_docx_with_properties(
         &self,
         tokens: TokenPackArg,
         values: ValuePackArg,
+        properties: PropertyPackArg,
         output_pattern: &str,
     ) -> DocxResult<()> {
-        let out_str = replace_tokens(output_pattern, tokens, values);
+        let tokens = &self.normalized_tokens(tokens);
+        let output_pattern = &self.token_names.normalize(output_pattern, &self.delimiters);
+        let defaults = self.defaults_of(tokens);
+        let types = self.types_of(tokens);
+        let builtins = self.builtins(output_pattern, properties);
+        validations::validate_single(tokens, values, &defaults, &types, &builtins, output_pattern)?;
+        let properties = self.normalized_properties(properties)?;
+        let values = with_defaults(values, &defaults);
+        let (tokens, values) = builtins.complete(tokens, &values, 1);
+        self.data_to_docx(&tokens, &values, &properties, output_pattern)?
+            .commit()
+    }
 
-        let out_path = PathBuf::from(&out_str);
-        if out_path.exists() {
-            let args: lang::TrArgVec = vec![("filename".to_string(), out_str)];
-            let msg = lang::tr_with_args("docx-filler-fail-overwrite", &args);
-            return Err(DocxError::Processing(msg));
-        }
+    /// Verifies that the document properties to be set can be set (see `properties::validate_properties`),
+    /// and normalizes the token names in their values.
+    ///
+    /// # Errors
+    ///
+    /// Can return Docx::Validation on failure, with details in message.
+    fn normalized_properties(&self, properties: PropertyPackArg) -> DocxResult<PropertyPack> {
+        let custom = self.part_contents(properties::CUSTOM_PART);
+        properties::validate_properties(properties, custom.as_deref())?;
+        Ok(properties
+            .iter()
+            .map(|(name, value)| {
+                let value = self.token_names.normalize(value, &self.delimiters);
+                (name.to_string(), value)
+            })
+            .collect())
+    }
 
-        let zip_file = File::create(out_path)?;
-        let mut zip = zip::ZipWriter::new(zip_file);
+    /// Common executive method for processing one docx file generation form the loaded template.
+    /// Generated file is staged, to be moved into place by committing it.
+    ///
+    /// # Arguments
+    ///
+    /// * `tokens` - vector of tokens to be replaced
+    /// * `values` - vector of values to be filled in place of tokens
+    /// * `properties` - document properties to be set explicitly, as name & value pairs
+    /// * `output_pattern` - output file pattern (explicit string or pattern contains tokens)
+    ///
+    /// # Errors
+    ///
+    /// Can return errors on inconsistent input data or other internal problems (see error message for details).
+    fn data_to_docx(
+        &self,
+        tokens: TokenPackArg,
+        values: ValuePackArg,
+        properties: PropertyPackArg,
+        output_pattern: &str,
+    ) -> DocxResult<staging::StagedFile> {
+        let out_path = output_path(output_pattern, tokens, values)?;
+        let (tokens, values) = self.filled_tokens(tokens, values);
 
-        let options = zip::write::FileOptions::default()
-            .compression_method(zip::CompressionMethod::Deflated)
-            .unix_permissions(0o755);
+        let mut media = images::Media::new(self.last_drawing_id);
+        let filled_parts = self.fill_parts(&tokens, &values, properties, &mut media)?;
+        self.write_docx(&out_path, &filled_parts, &media)
+    }
 
-        for (file_name, file_content) in self.file_data.iter() {
-            zip.start_file(file_name, options)?;
-            zip.write_all(file_content.as_bytes())?;
+    /// Fills the tokens of all the document text parts, setting the document properties.
+    ///
+    /// # Arguments
+    ///
+    /// * `tokens` - vector of tokens to be replaced
+    /// * `values` - vector of values to be filled in place of tokens
+    /// * `properties` - document properties to be set explicitly, as name & value pairs
+    /// * `media` - images embedded into the generated document so far
+    ///
+    /// # Errors
+    ///
+    /// Can return Docx::Processing on failure, with details in message.
+    fn fill_parts(
+        &self,
+        tokens: TokenPackArg,
+        values: ValuePackArg,
+        properties: PropertyPackArg,
+        media: &mut images::Media,
+    ) -> DocxResult<Vec<(String, String)>> {
+        // document parts go before their relationships, so all the embedded images are known for the latter
+        let mut filled_parts: Vec<(String, String)> = Default::default();
+        for part in self.target_parts.iter() {
+            let compiled = self
+                .compiled_parts
+                .iter()
+                .find(|(name, _)| name == part)
+                .map(|(_, compiled)| compiled);
+            let filled = match compiled {
+                Some(compiled) if !properties::is_affected(part, properties) => {
+                    self.render_part(part, compiled, tokens, values, media)
+                }
+                _ => match self.part_contents(part) {
+                    Some(contents) => {
+                        let contents = properties::set_properties(part, &contents, properties);
+                        self.fill_part(part, &contents, tokens, values, media)?
+                    }
+                    None => continue,
+                },
+            };
+            filled_parts.push((part.to_string(), filled));
         }
+        Ok(filled_parts)
+    }
 
-        let orig_document = self
-            .document_contents()
-            .ok_or_else(|| DocxError::Processing(lang::tr("docx-filler-fail-load")))?;
+    /// Writes the generated DOCX file into a temporary file in the directory of its final path,
+    /// to be moved into place by committing it (see `write_parts`).
+    ///
+    /// # Arguments
+    ///
+    /// * `out_path` - path of the generated file
+    /// * `filled_parts` - contents of the filled (or otherwise modified) parts, by the part names
+    /// * `media` - images embedded into the generated document
+    ///
+    /// # Errors
+    ///
+    /// Can return I/O or ZIP related errors, if the file cannot be written.
+    fn write_docx(
+        &self,
+        out_path: &Path,
+        filled_parts: &[(String, String)],
+        media: &images::Media,
+    ) -> DocxResult<staging::StagedFile> {
+        staging::StagedFile::write(out_path, |file| self.write_parts(file, filled_parts, media))
+    }
 
-        let updated_document = replace_tokens(&orig_document, tokens, values);
-        zip.start_file(&self.target_xml, options)?;
-        zip.write_all(updated_document.as_bytes())?;
+    /// Writes the DOCX contents into the file - parts of the template, with the filled parts in place
+    /// of the template ones. Filled parts not present in the template are added, along with the embedded images.
+    ///
+    /// # Arguments
+    ///
+    /// * `file` - file the DOCX contents are written into
+    /// * `filled_parts` - contents of the filled (or otherwise modified) parts, by the part names
+    /// * `media` - images embedded into the generated document
+    ///
+    /// # Errors
+    ///
+    /// Can return I/O or ZIP related errors, if the file cannot be written, or Docx::Processing
+    /// if any of the document text parts is missing from the filled parts.
+    fn write_parts(
+        &self,
+        file: &mut File,
+        filled_parts: &[(String, String)],
+        media: &images::Media,
+    ) -> DocxResult<()> {
+        let mut zip = zip::ZipWriter::new(file);
+
+        let mut existing: Vec<String> = Default::default();
+        for part in self.file_data.iter() {
+            existing.push(part.name.to_string());
+            if part.name.ends_with('/') {
+                zip.add_directory(&part.name, part.zip_options())?;
+                continue;
+            }
+
+            let filled = filled_parts.iter().find(|(name, _)| *name == part.name);
+            let data = match (&part.data, filled) {
+                (_, Some((_, contents))) => contents.as_bytes(),
+                (PartData::Text(_), None) => {
+                    let args: lang::TrArgVec = vec![("part".to_string(), part.name.to_string())];
+                    let msg = lang::tr_with_args("docx-filler-part-not-filled", &args);
+                    return Err(DocxError::Processing(msg));
+                }
+                (PartData::Binary(data), None) => data.as_slice(),
+            };
+            zip.start_file(&part.name, part.zip_options())?;
+            if part.name == CONTENT_TYPES_PART && !media.is_empty() {
+                let contents = String::from_utf8_lossy(data);
+                zip.write_all(images::add_content_types(&contents, media).as_bytes())?;
+            } else {
+                zip.write_all(data)?;
+            }
+        }
+        for (name, contents) in filled_parts.iter() {
+            if !existing.contains(name) {
+                zip.start_file(name, zip::write::FileOptions::default())?;
+                zip.write_all(contents.as_bytes())?;
+                existing.push(name.to_string());
+            }
+        }
+        for (name, data) in media.new_parts(&existing) {
+            zip.start_file(name, zip::write::FileOptions::default())?;
+            zip.write_all(&data)?;
+        }
         zip.finish()?;
 
         Ok(())
     }
 
+    /// Fills the tokens of a single document text part - repeats the table rows of loops,
+    /// resolves the conditional blocks, embeds the images, and fills in the values (see `render_part`).
+    ///
+    /// # Arguments
+    ///
+    /// * `part` - name of the document part, e.g. `word/document.xml`
+    /// * `contents` - contents of the document part in the template
+    /// * `tokens` - vector of tokens to be replaced
+    /// * `values` - vector of values to be filled in place of tokens
+    /// * `media` - images embedded into the generated document so far
+    ///
+    /// # Errors
+    ///
+    /// Can return Docx::Processing on failure, with details in message.
+    fn fill_part(
+        &self,
+        part: &str,
+        contents: &str,
+        tokens: TokenPackArg,
+        values: ValuePackArg,
+        media: &mut images::Media,
+    ) -> DocxResult<String> {
+        let delims = &self.delimiters;
+        let expanded_content =
+            loops::expand_loops(contents, &self.list_fields, delims, tokens, values)?;
+        let resolved_content =
+            blocks::resolve_conditions(&expanded_content, delims, tokens, values)?;
+        let embedded_content = if part.ends_with(".rels") {
+            resolved_content
+        } else {
+            images::embed_images(&resolved_content, part, delims, tokens, values, media)?
+        };
+        let compiled = engine::CompiledXml::compile(&embedded_content, delims);
+        Ok(self.render_part(part, &compiled, tokens, values, media))
+    }
+
+    /// Fills in the values of the compiled document text part in a single pass, and finishes the part -
+    /// relationships of the embedded images are added to the relationships part, and whitespace
+    /// of the filled in text is preserved. Escaped open delimiters are turned into the literal text
+    /// (or kept escaped in partial fill mode).
+    ///
+    /// # Arguments
+    ///
+    /// * `part` - name of the document part, e.g. `word/document.xml`
+    /// * `compiled` - compiled contents of the document part
+    /// * `tokens` - vector of tokens to be replaced
+    /// * `values` - vector of values to be filled in place of tokens
+    /// * `media` - images embedded into the generated document so far
+    fn render_part(
+        &self,
+        part: &str,
+        compiled: &engine::CompiledXml,
+        tokens: TokenPackArg,
+        values: ValuePackArg,
+        media: &images::Media,
+    ) -> String {
+        let filled = compiled.render(tokens, values, engine::Output::Document(self.fill_mode));
+        if part.ends_with(".rels") {
+            images::add_relationships(&filled, part, media)
+        } else {
+            runs::preserve_spaces(&filled)
+        }
+    }
+
     /// Generates batch of DOCX files form  the loaded template, one per each line of values in the input text.
+    /// Documents are generated in parallel (see `set_workers`) - after the first failure, documents of the lines
+    /// not started yet are not generated anymore, and the first error in the order of the lines is returned.
+    /// In the transactional batch mode (see `set_batch_mode`), the documents are moved into place only
+    /// when all of them are generated, so no document is kept on a failure.
+    /// In the combined batch output, a single DOCX file is generated instead, with the documents of all the lines
+    /// one after another, each of them starting on a new page.
     ///
     /// # Arguments
     ///
@@ -213,32 +1084,383 @@ impl DocxTemplate {
         separator: &str,
         output_pattern: &str,
     ) -> DocxResult<()> {
-        validations::validate_batch(tokens, text, separator, output_pattern)?;
+        self.build_docx_batch_with_properties(tokens, text, separator, &[], output_pattern)
+    }
 
-        for line in text.lines() {
-            let values = string_to_values(line, separator);
-            self.data_to_docx(tokens, &values, output_pattern)?;
+    /// Generates batch of DOCX files form the loaded template, setting their document properties explicitly.
+    /// Property values can contain tokens, filled with the values of each line (e.g. title `Contract of {{NAME}}`).
+    /// Combined document gets the properties filled with the values of the first line.
+    /// See `build_docx_batch` and `build_docx_with_properties` for details.
+    ///
+    /// # Arguments
+    ///
+    /// * `tokens` - vector of tokens to be replaced
+    /// * `text` - one to many lines of text - a set of values per each line for a new document to be generated
+    /// * `separator` - separator of the values within each line
+    /// * `properties` - document properties to be set, as name & value pairs (e.g. `title` and `Contract`)
+    /// * `output_pattern` - output file pattern (explicit string or pattern contains tokens)
+    ///
+    /// # Errors
+    ///
+    /// Can return error on failure, with details in the error message.
+    pub fn build_docx_batch_with_properties(
+        &self,
+        tokens: TokenPackArg,
+        text: &str,
+        separator: &str,
+        properties: PropertyPackArg,
+        output_pattern: &str,
+    ) -> DocxResult<()> {
+        let tokens = &self.normalized_tokens(tokens);
+        let output_pattern = &self.token_names.normalize(output_pattern, &self.delimiters);
+        let defaults = self.defaults_of(tokens);
+        let types = self.types_of(tokens);
+        let builtins = self.builtins(output_pattern, properties);
+        let format = self.line_format(tokens, separator);
+        validations::validate_batch(
+            tokens,
+            &defaults,
+            &types,
+            &builtins,
+            text,
+            &format,
+            output_pattern,
+            self.batch_output,
+        )?;
+        let properties = &self.normalized_properties(properties)?;
+        if self.batch_output == BatchOutput::Combined {
+            return self.lines_to_combined_docx(
+                tokens,
+                &defaults,
+                &builtins,
+                text,
+                &format,
+                properties,
+                output_pattern,
+            );
         }
 
-        Ok(())
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(self.workers)
+            .build()
+            .map_err(|err| DocxError::Processing(err.to_string()))?;
+        let lines: Vec<&str> = text.lines().collect();
+        // lines not started before the first failure are skipped - the failure is reported anyway
+        let failed = AtomicBool::new(false);
+        let results: Vec<DocxResult<Option<staging::StagedFile>>> = pool.install(|| {
+            lines
+                .par_iter()
+                .enumerate()
+                .map(|(i, line)| {
+                    if failed.load(Ordering::Relaxed) {
+                        return Ok(None);
+                    }
+                    let values = with_defaults(&string_to_values(line, &format), &defaults);
+                    let (tokens, values) = builtins.complete(tokens, &values, i + 1);
+                    let result = self
+                        .data_to_docx(&tokens, &values, properties, output_pattern)
+                        .and_then(|staged| match self.batch_mode {
+                            BatchMode::Progressive => staged.commit().map(|_| None),
+                            BatchMode::Transactional => Ok(Some(staged)),
+                        });
+                    if result.is_err() {
+                        failed.store(true, Ordering::Relaxed);
+                    }
+                    result
+                })
+                .collect()
+        });
+
+        // the first error of the lines in the input order is reported, dropping the staged files of all the lines
+        let staged: Vec<Option<staging::StagedFile>> =
+            results.into_iter().collect::<DocxResult<_>>()?;
+        staging::commit_all(staged.into_iter().flatten().collect())
     }
+
+    /// Generates a single DOCX file combining the documents of all the lines of values in the input text.
+    /// Output file pattern and the document properties are filled with values of the first line.
+    ///
+    /// # Arguments
+    ///
+    /// * `tokens` - vector of tokens to be replaced
+    /// * `defaults` - default values of tokens declared in the template
+    /// * `builtins` - built-in tokens used in the template and output pattern
+    /// * `text` - one to many lines of text - a set of values per each line for a new document to be generated
+    /// * `format` - format of each line of input - value separator, and which of the values are lists
+    /// * `properties` - document properties to be set explicitly, as name & value pairs
+    /// * `output_pattern` - output file pattern (explicit string or pattern contains tokens)
+    ///
+    /// # Errors
+    ///
+    /// Can return error on failure, with details in the error message.
+    #[allow(clippy::too_many_arguments)]
+    fn lines_to_combined_docx(
+        &self,
+        tokens: TokenPackArg,
+        defaults: DefaultPackArg,
+        builtins: &builtins::Builtins,
+        text: &str,
+        format: &LineFormat,
+        properties: PropertyPackArg,
+        output_pattern: &str,
+    ) -> DocxResult<()> {
+        let content_types = self.binary_contents(CONTENT_TYPES_PART).unwrap_or_default();
+        let numbering = self.binary_contents(combine::NUMBERING_PART);
+        let mut combined =
+            combine::CombinedDocument::new((CONTENT_TYPES_PART, content_types), numbering);
+
+        let mut media = images::Media::new(self.last_drawing_id);
+        let mut out_path: Option<PathBuf> = None;
+        for (i, line) in text.lines().enumerate() {
+            let values = with_defaults(&string_to_values(line, format), defaults);
+            let (tokens, values) = builtins.complete(tokens, &values, i + 1);
+            if out_path.is_none() {
+                out_path = Some(output_path(output_pattern, &tokens, &values)?);
+            }
+
+            let (tokens, values) = self.filled_tokens(&tokens, &values);
+            let mut parts = self.fill_parts(&tokens, &values, properties, &mut media)?;
+            parts.extend(media.new_relationships(&self.target_parts));
+            combined.append(parts);
+        }
+
+        match out_path {
+            Some(out_path) => self
+                .write_docx(&out_path, &combined.into_parts(), &media)?
+                .commit(),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Returns path of the generated file, filling the tokens of the output file pattern.
+///
+/// # Errors
+///
+/// Can return Docx::Processing if the file already exists.
+fn output_path(
+    output_pattern: &str,
+    tokens: TokenPackArg,
+    values: ValuePackArg,
+) -> DocxResult<PathBuf> {
+    let out_str = replace_tokens(output_pattern, tokens, values);
+
+    let out_path = PathBuf::from(&out_str);
+    if out_path.exists() {
+        return Err(staging::overwrite_error(&out_str));
+    }
+    Ok(out_path)
 }
 
-/// Fill in the input string with specified set of tokens and values.
+/// Returns plain token texts of the template tokens, keeping their order.
+pub fn token_names(tokens: &[TemplateToken]) -> TokenPack {
+    tokens.iter().map(|t| t.token.to_string()).collect()
+}
+
+/// Lists byte ranges of the DOCX part contents, where tokens are to be looked for -
+/// text of the document and descriptions of placeholder pictures, targets of the relationships
+/// (e.g. hyperlink addresses), or values of the document properties.
+fn searchable_ranges(part: &str, contents: &str) -> Vec<Range<usize>> {
+    if part.ends_with(".rels") {
+        xml::target_ranges(contents)
+    } else if part.starts_with("docProps/") {
+        xml::text_ranges(contents)
+    } else {
+        let mut ranges: Vec<Range<usize>> = runs::text_nodes(contents)
+            .into_iter()
+            .map(|node| node.content)
+            .chain(images::placeholder_ranges(contents))
+            .collect();
+        ranges.sort_by_key(|range| range.start);
+        ranges
+    }
+}
+
+/// Fill in the input string with specified set of tokens and values, in a single pass
+/// (so that a value containing text of another token is filled in just as it is).
 fn replace_tokens(input: &str, tokens: TokenPackArg, values: ValuePackArg) -> String {
     assert_eq!(tokens.len(), values.len());
-    let mut output: String = input.to_string();
-    for i in 0..tokens.len() {
-        output = output.replace(&tokens[i], &values[i]);
+    let mut alternatives: Vec<&String> = tokens.iter().filter(|token| !token.is_empty()).collect();
+    if alternatives.is_empty() {
+        return input.to_string();
     }
-    output
+    // longer tokens first, so that a token is never replaced by the part of it
+    alternatives.sort_by_key(|token| std::cmp::Reverse(token.len()));
+    let pattern: Vec<String> = alternatives
+        .iter()
+        .map(|token| regex::escape(token))
+        .collect();
+    let token_re = match Regex::new(&pattern.join("|")) {
+        Ok(token_re) => token_re,
+        Err(_) => return input.to_string(),
+    };
+    token_re
+        .replace_all(input, |cap: &regex::Captures| {
+            let i = tokens
+                .iter()
+                .position(|token| *token == cap[0])
+                .unwrap_or_default();
+            values[i].text()
+        })
+        .to_string()
 }
 
-/// Parse the input string into set of values.
-fn string_to_values(input: &str, separator: &str) -> ValuePack {
-    let values: ValuePack = input
-        .split(separator)
-        .map(|x| x.trim().to_string())
-        .collect();
+/// Completes the values with the default ones - values omitted at the end are added,
+/// and the empty values of tokens with default are replaced by the default.
+fn with_defaults(values: ValuePackArg, defaults: DefaultPackArg) -> ValuePack {
+    let mut values = values.to_vec();
+    if values.len() < defaults.len() {
+        values.resize(defaults.len(), Default::default());
+    }
+    for (value, default) in values.iter_mut().zip(defaults) {
+        if let Some(default) = default {
+            if value.text().trim().is_empty() {
+                *value = Value::from(default.as_str());
+            }
+        }
+    }
     values
 }
+
+/// Parse the input string into set of values.
+/// Value of a list token enclosed in square brackets is a list (see `string_to_list`), separator is not looked for
+/// inside of it. Square brackets of other values (or unbalanced ones) are a plain text.
+fn string_to_values(input: &str, format: &LineFormat) -> ValuePack {
+    let mut values: ValuePack = Default::default();
+    let mut rest = input;
+    loop {
+        let is_list = format.lists.get(values.len()).copied().unwrap_or(false);
+        let list_end = match is_list {
+            true => bracketed_end(rest),
+            false => None,
+        };
+        let search_from = list_end.unwrap_or(0);
+        let found = match format.separator.is_empty() {
+            true => None,
+            false => rest[search_from..].find(&format.separator),
+        };
+        let (value, next) = match found {
+            Some(pos) => (
+                &rest[..search_from + pos],
+                Some(&rest[search_from + pos + format.separator.len()..]),
+            ),
+            None => (rest, None),
+        };
+
+        let value = value.trim();
+        values.push(
+            match value.strip_prefix('[').and_then(|x| x.strip_suffix(']')) {
+                Some(list) if list_end.is_some() => Value::List(string_to_list(list)),
+                _ => Value::Text(value.to_string()),
+            },
+        );
+        match next {
+            Some(next) => rest = next,
+            None => return values,
+        }
+    }
+}
+
+/// Returns the byte position following the closing square bracket of the input string starting
+/// (after whitespace) with the opening one, if the brackets are balanced.
+fn bracketed_end(input: &str) -> Option<usize> {
+    let start = input.len() - input.trim_start().len();
+    if !input[start..].starts_with('[') {
+        return None;
+    }
+    let mut depth: usize = 0;
+    for (i, ch) in input[start..].char_indices() {
+        match ch {
+            '[' => depth += 1,
+            ']' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(start + i + 1);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Parse the input string into list items - items are separated by `|`, values of each item by `,`
+/// (e.g. `2, apples | 3, pears`).
+fn string_to_list(input: &str) -> Vec<ValuePack> {
+    if input.trim().is_empty() {
+        return Default::default();
+    }
+    input
+        .split(LIST_ITEM_SEPARATOR)
+        .map(|item| {
+            item.split(LIST_VALUE_SEPARATOR)
+                .map(|x| Value::Text(x.trim().to_string()))
+                .collect()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn format(separator: &str, lists: &[bool]) -> LineFormat {
+        LineFormat {
+            separator: separator.to_string(),
+            lists: lists.to_vec(),
+        }
+    }
+
+    fn texts(values: &[Value]) -> Vec<String> {
+        values.iter().map(Value::text).collect()
+    }
+
+    #[test]
+    fn splits_values_by_separator() {
+        let values = string_to_values(" Ann ; 5 EUR;", &format(";", &[false, false, false]));
+        assert_eq!(texts(&values), vec!["Ann", "5 EUR", ""]);
+    }
+
+    #[test]
+    fn keeps_brackets_of_plain_values() {
+        let values = string_to_values("Note [1 ; y", &format(";", &[false, false]));
+        assert_eq!(texts(&values), vec!["Note [1", "y"]);
+        let values = string_to_values("[draft]; x", &format(";", &[false, false]));
+        assert_eq!(values[0], Value::from("[draft]"));
+    }
+
+    #[test]
+    fn parses_lists_of_list_tokens() {
+        let values = string_to_values(
+            "Ann; [2, a; b | 3, c]; x",
+            &format(";", &[false, true, false]),
+        );
+        assert_eq!(values.len(), 3);
+        assert_eq!(
+            values[1],
+            Value::List(vec![
+                vec![Value::from("2"), Value::from("a; b")],
+                vec![Value::from("3"), Value::from("c")],
+            ])
+        );
+        assert_eq!(values[2], Value::from("x"));
+    }
+
+    #[test]
+    fn reads_unbalanced_list_as_text() {
+        let values = string_to_values("[1, a; x", &format(";", &[true, false]));
+        assert_eq!(texts(&values), vec!["[1, a", "x"]);
+    }
+
+    #[test]
+    fn parses_empty_list() {
+        let values = string_to_values("[]", &format(";", &[true]));
+        assert_eq!(values, vec![Value::List(vec![])]);
+    }
+
+    #[test]
+    fn keeps_whole_line_without_separator() {
+        let values = string_to_values("a; b", &format("", &[false]));
+        assert_eq!(texts(&values), vec!["a; b"]);
+    }
+}
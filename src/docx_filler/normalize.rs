@@ -0,0 +1,256 @@
+use super::delimiters::Delimiters;
+use super::types;
+use regex::Regex;
+use std::ops::Range;
+use unicode_normalization::UnicodeNormalization;
+
+/// Separator of the list token and its field, e.g. `{{items.qty}}`.
+const FIELD_SEPARATOR: char = '.';
+/// Separators following the token name - of the default value, and of the formatters.
+const NAME_ENDS: [&str; 2] = ["?=", "|"];
+
+lazy_static! {
+    /// Matches text (between the delimiters) of the opening markers of the blocks and repeated rows,
+    /// with the name of the token they use, e.g. `#if NAME` or `#each items`.
+    static ref MARKER_RE: Regex = Regex::new(r"^(#(?:if|unless|each))\s+(.+)$").unwrap();
+}
+
+/// Policy of identifying the tokens of the template - which variants of the token name are the same token.
+/// Variants of the name are replaced by its first occurrence in the template (e.g. `{{ writer}}`
+/// by `{{WRITER}}`), so they are all discovered as one token, and filled by one value.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TokenNormalization {
+    /// whether the whitespace around the token name is trimmed, and the inner one collapsed to a single space
+    /// (e.g. `{{ WRITER}}` is the same token as `{{WRITER}}`).
+    pub trim_whitespace: bool,
+    /// whether the letter case of the token name is ignored (e.g. `{{Writer}}` is the same token as `{{WRITER}}`).
+    pub ignore_case: bool,
+    /// whether the token names are compared in the same Unicode normalization form (NFC),
+    /// e.g. `é` typed as a single character, or as `e` followed by combining accent.
+    pub unicode_form: bool,
+}
+
+impl Default for TokenNormalization {
+    fn default() -> Self {
+        TokenNormalization {
+            trim_whitespace: true,
+            ignore_case: false,
+            unicode_form: false,
+        }
+    }
+}
+
+/// Token names of the template, normalized by the policy - each one in the variant of its first occurrence.
+#[derive(Debug, Clone, Default)]
+pub struct TokenNames {
+    /// policy of identifying the tokens.
+    policy: TokenNormalization,
+    /// names found in the template, in the variant of the first occurrence, along with their comparison keys.
+    names: Vec<(String, String)>,
+}
+
+impl TokenNames {
+    /// Creates the empty set of token names, normalized by the policy.
+    pub fn new(policy: TokenNormalization) -> TokenNames {
+        TokenNames {
+            policy,
+            names: Default::default(),
+        }
+    }
+
+    /// Replaces variants of the token names in the specified byte ranges of the template part XML
+    /// (e.g. `{{ writer|upper}}`) by the first occurrence of the name (e.g. `{{WRITER|upper}}`).
+    /// Names found for the first time are remembered.
+    ///
+    /// # Arguments
+    ///
+    /// * `xml` - contents of the template part
+    /// * `ranges` - byte ranges of the part contents, where tokens are to be looked for
+    /// * `delimiters` - delimiters of the tokens in the template
+    pub fn normalize_xml(
+        &mut self,
+        xml: &str,
+        ranges: &[Range<usize>],
+        delimiters: &Delimiters,
+    ) -> String {
+        let mut output = String::with_capacity(xml.len());
+        let mut last = 0;
+        for range in ranges {
+            for found in delimiters.token_re().find_iter(&xml[range.clone()]) {
+                let normalized = rename_token(found.as_str(), delimiters, &mut |name| {
+                    self.learn(name);
+                    self.canonical(name)
+                });
+                output.push_str(&xml[last..range.start + found.start()]);
+                output.push_str(&normalized);
+                last = range.start + found.end();
+            }
+        }
+        output.push_str(&xml[last..]);
+        output
+    }
+
+    /// Replaces variants of the token names in the text (e.g. output file pattern, or the token itself)
+    /// by the names found in the template. Names not found in the template are just normalized by the policy.
+    pub fn normalize(&self, text: &str, delimiters: &Delimiters) -> String {
+        delimiters
+            .token_re()
+            .replace_all(text, |cap: &regex::Captures| {
+                rename_token(&cap[0], delimiters, &mut |name| self.canonical(name))
+            })
+            .to_string()
+    }
+
+    /// Remembers the token name, if there is no variant of it yet.
+    fn learn(&mut self, name: &str) {
+        let key = self.key(name);
+        if !self.names.iter().any(|(_, known)| *known == key) {
+            let normal = self.normal_form(name);
+            self.names.push((normal, key));
+        }
+    }
+
+    /// Returns the variant of the token name found in the template first, or the name normalized by the policy.
+    fn canonical(&self, name: &str) -> String {
+        let key = self.key(name);
+        self.names
+            .iter()
+            .find(|(_, known)| *known == key)
+            .map_or_else(
+                || self.normal_form(name),
+                |(canonical, _)| canonical.to_string(),
+            )
+    }
+
+    /// Returns the token name with whitespace and Unicode form normalized by the policy.
+    fn normal_form(&self, name: &str) -> String {
+        let name = match self.policy.trim_whitespace {
+            true => name.split_whitespace().collect::<Vec<&str>>().join(" "),
+            false => name.to_string(),
+        };
+        match self.policy.unicode_form {
+            true => name.nfc().collect(),
+            false => name,
+        }
+    }
+
+    /// Returns the key of the token name, that is the same for all the variants of the name.
+    fn key(&self, name: &str) -> String {
+        let name = self.normal_form(name);
+        match self.policy.ignore_case {
+            true => name.to_lowercase(),
+            false => name,
+        }
+    }
+}
+
+/// Renames the token - each name it uses (the name itself, list and field names, or the name used by the marker)
+/// is replaced by the result of the `rename` function. Built-in tokens and closing markers are kept as they are.
+fn rename_token(
+    token: &str,
+    delimiters: &Delimiters,
+    rename: &mut dyn FnMut(&str) -> String,
+) -> String {
+    let inner = delimiters.inner(token);
+    let name_end = NAME_ENDS
+        .iter()
+        .filter_map(|end| inner.find(end))
+        .min()
+        .unwrap_or(inner.len());
+    let (head, rest) = inner.split_at(name_end);
+    let (name, kind) = types::split_type(head);
+    let kind = kind.map_or(String::new(), |kind| format!(":{}", kind.name()));
+
+    let renamed = match MARKER_RE.captures(name.trim()) {
+        Some(cap) => format!("{} {}", &cap[1], rename_fields(&cap[2], rename)),
+        None if name.trim().starts_with(['#', '/']) => return token.to_string(),
+        None => rename_fields(name, rename),
+    };
+    delimiters.wrap(&[&renamed, &kind, rest].concat())
+}
+
+/// Renames each part of the list field name (e.g. `items` and `qty` of `items.qty`), or the plain name.
+fn rename_fields(name: &str, rename: &mut dyn FnMut(&str) -> String) -> String {
+    name.split(FIELD_SEPARATOR)
+        .map(rename)
+        .collect::<Vec<String>>()
+        .join(&FIELD_SEPARATOR.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(policy: TokenNormalization, xml: &str) -> (TokenNames, String) {
+        let mut names = TokenNames::new(policy);
+        let whole = 0..xml.len();
+        let normalized =
+            names.normalize_xml(xml, std::slice::from_ref(&whole), &Delimiters::default());
+        (names, normalized)
+    }
+
+    #[test]
+    fn trims_whitespace_of_names() {
+        let (names, xml) = names(
+            Default::default(),
+            "{{ FIRST  NAME }} {{FIRST NAME|upper}} {{Writer}}",
+        );
+        assert_eq!(xml, "{{FIRST NAME}} {{FIRST NAME|upper}} {{Writer}}");
+        assert_eq!(
+            names.normalize("{{ Writer }}.docx", &Delimiters::default()),
+            "{{Writer}}.docx"
+        );
+    }
+
+    #[test]
+    fn uses_first_variant_ignoring_case() {
+        let policy = TokenNormalization {
+            ignore_case: true,
+            ..Default::default()
+        };
+        let (names, xml) = names(
+            policy,
+            "{{Writer}} {{WRITER:email?=x}} {{#if writer}}{{/if}}",
+        );
+        assert_eq!(xml, "{{Writer}} {{Writer:email?=x}} {{#if Writer}}{{/if}}");
+        assert_eq!(
+            names.normalize("{{wRiTeR}}", &Delimiters::default()),
+            "{{Writer}}"
+        );
+    }
+
+    #[test]
+    fn renames_list_fields() {
+        let policy = TokenNormalization {
+            ignore_case: true,
+            ..Default::default()
+        };
+        let (_, xml) = names(policy, "{{#each Items}}{{ITEMS.Qty}} {{items. qty }}");
+        assert_eq!(xml, "{{#each Items}}{{Items.Qty}} {{Items.Qty}}");
+    }
+
+    #[test]
+    fn compares_unicode_forms() {
+        let composed = "{{caf\u{E9}}}";
+        let decomposed = "{{cafe\u{301}}}";
+        let xml = [composed, decomposed].concat();
+        let (_, kept) = names(Default::default(), &xml);
+        assert_eq!(kept, xml);
+        let policy = TokenNormalization {
+            unicode_form: true,
+            ..Default::default()
+        };
+        let (_, normalized) = names(policy, &xml);
+        assert_eq!(normalized, [composed, composed].concat());
+    }
+
+    #[test]
+    fn keeps_whitespace_when_not_trimmed() {
+        let policy = TokenNormalization {
+            trim_whitespace: false,
+            ..Default::default()
+        };
+        let (_, xml) = names(policy, "{{ A}} {{A}}");
+        assert_eq!(xml, "{{ A}} {{A}}");
+    }
+}
@@ -0,0 +1,234 @@
+use super::{formats, xml, DocxError, DocxResult, PropertyPackArg};
+use crate::lang;
+use regex::Regex;
+use std::str::FromStr;
+
+/// Name of the DOCX part with the core document properties (title, author, ...).
+pub const CORE_PART: &str = "docProps/core.xml";
+/// Name of the DOCX part with the custom document properties.
+pub const CUSTOM_PART: &str = "docProps/custom.xml";
+
+/// Names of the core document properties, along with the elements holding them in the core properties part.
+const CORE_PROPERTIES: [(&str, &str); 8] = [
+    ("title", "dc:title"),
+    ("subject", "dc:subject"),
+    ("author", "dc:creator"),
+    ("creator", "dc:creator"),
+    ("keywords", "cp:keywords"),
+    ("description", "dc:description"),
+    ("category", "cp:category"),
+    ("status", "cp:contentStatus"),
+];
+
+/// Type of the custom property values holding any text.
+const TEXT_TYPE: &str = "vt:lpwstr";
+/// Format of the dates set as custom property values of the date type.
+const FILETIME_FORMAT: &str = "%Y-%m-%dT00:00:00Z";
+
+/// Closing tag of the core properties part root element.
+const CORE_CLOSING_TAG: &str = "</cp:coreProperties>";
+
+/// Sets the document properties of the DOCX part explicitly - core properties (e.g. `title` or `author`)
+/// in the core properties part, and the rest of them as custom properties in the custom properties part.
+/// Parts other than these are returned as they are.
+///
+/// # Arguments
+///
+/// * `part` - name of the DOCX part, e.g. `docProps/core.xml`
+/// * `xml` - contents of the DOCX part
+/// * `properties` - document properties to be set, as name & value pairs
+pub fn set_properties(part: &str, xml: &str, properties: PropertyPackArg) -> String {
+    let mut output = xml.to_string();
+    for (name, value) in properties {
+        match (core_element(name), part) {
+            (Some(element), CORE_PART) => output = set_core(&output, element, value),
+            (None, CUSTOM_PART) => {
+                if let Some(custom) = set_custom(&output, name, value) {
+                    output = custom;
+                }
+            }
+            _ => {}
+        }
+    }
+    output
+}
+
+/// Checks whether setting the document properties changes the DOCX part.
+pub fn is_affected(part: &str, properties: PropertyPackArg) -> bool {
+    !properties.is_empty() && (part == CORE_PART || part == CUSTOM_PART)
+}
+
+/// Verifies that all the custom document properties to be set are defined in the template
+/// (core properties can be set always).
+///
+/// # Arguments
+///
+/// * `properties` - document properties to be set, as name & value pairs
+/// * `custom_xml` - contents of the custom properties part of the template, if there is any
+///
+/// # Errors
+///
+/// Can return Docx::Validation on failure, with details in message.
+pub fn validate_properties(
+    properties: PropertyPackArg,
+    custom_xml: Option<&str>,
+) -> DocxResult<()> {
+    for (name, _) in properties {
+        let defined = core_element(name).is_some()
+            || custom_xml
+                .and_then(|xml| set_custom(xml, name, ""))
+                .is_some();
+        if !defined {
+            let args: lang::TrArgVec = vec![("name".to_string(), name.to_string())];
+            let msg = lang::tr_with_args("docx-filler-unknown-property", &args);
+            return Err(DocxError::Validation(msg));
+        }
+    }
+    Ok(())
+}
+
+/// Returns the element holding the core property of the given name, if it is a core property.
+fn core_element(name: &str) -> Option<&'static str> {
+    CORE_PROPERTIES
+        .iter()
+        .find(|(known, _)| known.eq_ignore_ascii_case(name.trim()))
+        .map(|(_, element)| *element)
+}
+
+/// Sets the value of the core property element - replacing its text, or adding the element if it is missing.
+fn set_core(xml: &str, element: &str, value: &str) -> String {
+    let escaped = xml::escape(value, xml::XmlContext::Text);
+    let re = Regex::new(&format!(
+        r"<{0}(?:\s[^>]*)?(?:/>|>[^<]*</{0}>)",
+        regex::escape(element)
+    ))
+    .unwrap();
+    let replacement = format!("<{0}>{1}</{0}>", element, escaped);
+    if re.is_match(xml) {
+        return re.replace(xml, regex::NoExpand(&replacement)).to_string();
+    }
+    match xml.rfind(CORE_CLOSING_TAG) {
+        Some(pos) => [&xml[..pos], &replacement, &xml[pos..]].concat(),
+        None => xml.to_string(),
+    }
+}
+
+/// Sets the value of the custom property of the given name, if the property is defined.
+/// Typed properties (numbers, yes/no, dates) keep their type, if the value can be converted to it
+/// (e.g. `yes` to `true`, or `1.4.2022` to `2022-04-01T00:00:00Z`), otherwise they become text properties.
+fn set_custom(xml: &str, name: &str, value: &str) -> Option<String> {
+    let attribute = xml::escape(name, xml::XmlContext::Attribute);
+    let re = Regex::new(&format!(
+        r#"(<property\s[^>]*\bname="{}"[^>]*>\s*)<(vt:\w+)>[^<]*</vt:\w+>"#,
+        regex::escape(&attribute)
+    ))
+    .unwrap();
+    let found = re.captures(xml)?;
+    let (open, element) = (found.get(1)?, found.get(0)?);
+    let (kind, value) = match typed_value(&found[2], value) {
+        Some(typed) => (&found[2], typed),
+        None => (TEXT_TYPE, value.to_string()),
+    };
+    let escaped = xml::escape(&value, xml::XmlContext::Text);
+    let typed_element = format!("<{0}>{1}</{0}>", kind, escaped);
+    Some([&xml[..open.end()], &typed_element, &xml[element.end()..]].concat())
+}
+
+/// Converts the value into the form of the custom property type (e.g. `vt:bool`), if it can be converted.
+/// Text types accept any value.
+fn typed_value(kind: &str, value: &str) -> Option<String> {
+    let trimmed = value.trim();
+    match kind {
+        "vt:lpwstr" | "vt:lpstr" | "vt:bstr" => Some(value.to_string()),
+        "vt:i1" => number::<i8>(trimmed),
+        "vt:i2" => number::<i16>(trimmed),
+        "vt:i4" | "vt:int" => number::<i32>(trimmed),
+        "vt:i8" => number::<i64>(trimmed),
+        "vt:ui1" => number::<u8>(trimmed),
+        "vt:ui2" => number::<u16>(trimmed),
+        "vt:ui4" | "vt:uint" => number::<u32>(trimmed),
+        "vt:ui8" => number::<u64>(trimmed),
+        "vt:r4" | "vt:r8" | "vt:decimal" => trimmed
+            .replace(',', ".")
+            .parse::<f64>()
+            .ok()
+            .filter(|number| number.is_finite())
+            .map(|number| number.to_string()),
+        "vt:bool" => match trimmed.to_lowercase().as_str() {
+            "true" | "yes" | "1" => Some("true".to_string()),
+            "false" | "no" | "0" => Some("false".to_string()),
+            _ => None,
+        },
+        "vt:filetime" => {
+            formats::parse_date(trimmed).map(|date| date.format(FILETIME_FORMAT).to_string())
+        }
+        _ => None,
+    }
+}
+
+/// Parses the number of the given type, returning it in its canonical form.
+fn number<T: FromStr + ToString>(value: &str) -> Option<String> {
+    value.parse::<T>().ok().map(|number| number.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn custom(kind: &str, value: &str) -> String {
+        format!(
+            r#"<Properties><property pid="2" name="Ref"><{0}>{1}</{0}></property></Properties>"#,
+            kind, value
+        )
+    }
+
+    #[test]
+    fn sets_text_property() {
+        assert_eq!(
+            set_custom(&custom("vt:lpwstr", "x"), "Ref", "A & B"),
+            Some(custom("vt:lpwstr", "A &amp; B"))
+        );
+        assert_eq!(set_custom(&custom("vt:lpwstr", "x"), "Other", "A"), None);
+    }
+
+    #[test]
+    fn keeps_type_of_convertible_values() {
+        let set = |kind: &str, value: &str| set_custom(&custom(kind, "0"), "Ref", value).unwrap();
+        assert_eq!(set("vt:i4", " 42 "), custom("vt:i4", "42"));
+        assert_eq!(set("vt:r8", "1,50"), custom("vt:r8", "1.5"));
+        assert_eq!(set("vt:bool", "Yes"), custom("vt:bool", "true"));
+        assert_eq!(
+            set("vt:filetime", "1.4.2022"),
+            custom("vt:filetime", "2022-04-01T00:00:00Z")
+        );
+    }
+
+    #[test]
+    fn turns_inconvertible_values_into_text() {
+        let set = |kind: &str, value: &str| set_custom(&custom(kind, "0"), "Ref", value).unwrap();
+        assert_eq!(set("vt:i4", "{{AGE}}"), custom("vt:lpwstr", "{{AGE}}"));
+        assert_eq!(
+            set("vt:i4", "3000000000"),
+            custom("vt:lpwstr", "3000000000")
+        );
+        assert_eq!(set("vt:bool", "maybe"), custom("vt:lpwstr", "maybe"));
+        assert_eq!(set("vt:filetime", "soon"), custom("vt:lpwstr", "soon"));
+    }
+
+    #[test]
+    fn sets_core_property() {
+        let xml = "<cp:coreProperties><dc:title>Old</dc:title></cp:coreProperties>";
+        assert_eq!(
+            set_properties(
+                CORE_PART,
+                xml,
+                &[("title".to_string(), "<New>".to_string())]
+            ),
+            "<cp:coreProperties><dc:title>&lt;New&gt;</dc:title></cp:coreProperties>"
+        );
+        assert_eq!(
+            set_properties(CORE_PART, xml, &[("Author".to_string(), "Ann".to_string())]),
+            "<cp:coreProperties><dc:title>Old</dc:title><dc:creator>Ann</dc:creator></cp:coreProperties>"
+        );
+    }
+}
@@ -0,0 +1,219 @@
+use super::{runs, xml};
+use std::ops::Range;
+
+/// Maximal length (in characters) of the text snippet around the token occurrence.
+const SNIPPET_CHARS: usize = 60;
+/// Mark of the snippet text cut off at its start or end.
+const ELLIPSIS: &str = "\u{2026}";
+
+/// Single occurrence of a token in the template, with details on where it was found.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TokenOccurrence {
+    /// name of the DOCX part containing the token, e.g. `word/header1.xml`.
+    pub part: String,
+    /// sequential number of the paragraph within the part, starting from 1
+    /// (`None` outside of paragraphs, e.g. in hyperlink address).
+    pub paragraph: Option<usize>,
+    /// whether the token sits in a table.
+    pub in_table: bool,
+    /// whether the token sits in a header or footer.
+    pub in_header: bool,
+    /// whether the token sits in a text box.
+    pub in_text_box: bool,
+    /// snippet of the text around the token, e.g. `Dear {{NAME}}, let me congratulate you…`.
+    pub context: String,
+}
+
+/// Token of the template, along with all of its occurrences.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TokenReport {
+    /// the (base) token itself, e.g. `{{NAME}}` (for both `{{NAME}}` and `{{NAME|upper}}`).
+    pub token: String,
+    /// occurrences of the token, in the document order (main document first).
+    pub occurrences: Vec<TokenOccurrence>,
+}
+
+impl TokenReport {
+    /// Returns number of the token occurrences in the template.
+    pub fn count(&self) -> usize {
+        self.occurrences.len()
+    }
+}
+
+/// Alias for a set of token reports, in order of the first occurrence of the tokens.
+pub type TokenReportPack = Vec<TokenReport>;
+
+/// Elements of a single DOCX part, that the token occurrences are located by.
+pub struct PartLayout {
+    /// name of the DOCX part, e.g. `word/document.xml`.
+    part: String,
+    /// byte ranges of the paragraphs, in order of their start.
+    paragraphs: Vec<Range<usize>>,
+    /// byte ranges of the tables.
+    tables: Vec<Range<usize>>,
+    /// byte ranges of the text box contents.
+    text_boxes: Vec<Range<usize>>,
+}
+
+impl PartLayout {
+    /// Finds the elements of the DOCX part XML, that the token occurrences are located by.
+    pub fn new(part: &str, xml: &str) -> PartLayout {
+        PartLayout {
+            part: part.to_string(),
+            paragraphs: xml::element_ranges(xml, "w:p"),
+            tables: xml::element_ranges(xml, "w:tbl"),
+            text_boxes: xml::element_ranges(xml, "w:txbxContent"),
+        }
+    }
+
+    /// Describes the token occurrence found at the byte range of the part XML.
+    pub fn occurrence(&self, xml: &str, range: &Range<usize>) -> TokenOccurrence {
+        let encloses = |outer: &Range<usize>| outer.start <= range.start && range.end <= outer.end;
+        let paragraph = self
+            .paragraphs
+            .iter()
+            .enumerate()
+            .rev()
+            .find(|(_, paragraph)| encloses(paragraph));
+        let context = match paragraph {
+            Some((_, paragraph)) => runs::visible_text(&xml[paragraph.clone()]),
+            None => xml[range.clone()].to_string(),
+        };
+        let name = self
+            .part
+            .trim_start_matches("word/")
+            .trim_start_matches("_rels/");
+        TokenOccurrence {
+            part: self.part.to_string(),
+            paragraph: paragraph.map(|(i, _)| i + 1),
+            in_table: self.tables.iter().any(encloses),
+            in_header: name.starts_with("header") || name.starts_with("footer"),
+            in_text_box: self.text_boxes.iter().any(encloses),
+            context: snippet(
+                &xml::unescape(&context),
+                &xml::unescape(&xml[range.clone()]),
+            ),
+        }
+    }
+}
+
+/// Cuts the snippet of the text around the token, at most `SNIPPET_CHARS` long (ellipsis aside).
+fn snippet(text: &str, token: &str) -> String {
+    let text = text.trim();
+    let chars: Vec<char> = text.chars().collect();
+    if chars.len() <= SNIPPET_CHARS {
+        return text.to_string();
+    }
+    let token_start = text
+        .find(token)
+        .map_or(0, |pos| text[..pos].chars().count());
+    let token_len = token.chars().count();
+    let margin = SNIPPET_CHARS.saturating_sub(token_len) / 2;
+    let start = token_start
+        .saturating_sub(margin)
+        .min(chars.len() - SNIPPET_CHARS);
+    let end = (start + SNIPPET_CHARS)
+        .max(token_start + token_len)
+        .min(chars.len());
+    let mut snippet: String = chars[start..end].iter().collect();
+    if start > 0 {
+        snippet.insert_str(0, ELLIPSIS);
+    }
+    if end < chars.len() {
+        snippet.push_str(ELLIPSIS);
+    }
+    snippet
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn occurrence(part: &str, xml: &str, token: &str) -> TokenOccurrence {
+        let start = xml.find(token).unwrap();
+        PartLayout::new(part, xml).occurrence(xml, &(start..start + token.len()))
+    }
+
+    fn paragraph(text: &str) -> String {
+        format!("<w:p><w:pPr/><w:r><w:t>{}</w:t></w:r></w:p>", text)
+    }
+
+    #[test]
+    fn locates_token_in_header_paragraph() {
+        let xml = [paragraph("a"), paragraph("Hi &amp; welcome {{A}}")].concat();
+        let found = occurrence("word/header1.xml", &xml, "{{A}}");
+        assert_eq!(
+            found,
+            TokenOccurrence {
+                part: "word/header1.xml".to_string(),
+                paragraph: Some(2),
+                in_table: false,
+                in_header: true,
+                in_text_box: false,
+                context: "Hi & welcome {{A}}".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn locates_token_in_table_and_text_box() {
+        let table = format!(
+            "<w:tbl><w:tr><w:tc>{}</w:tc></w:tr></w:tbl>",
+            paragraph("{{A}}")
+        );
+        let text_box = format!(
+            "<w:p><w:r><w:pict><v:textbox><w:txbxContent>{}</w:txbxContent></v:textbox></w:pict></w:r></w:p>",
+            paragraph("{{B}}")
+        );
+        let xml = [paragraph("x"), table, text_box].concat();
+
+        let found = occurrence("word/document.xml", &xml, "{{A}}");
+        assert_eq!(found.paragraph, Some(2));
+        assert!(found.in_table && !found.in_text_box && !found.in_header);
+
+        let found = occurrence("word/document.xml", &xml, "{{B}}");
+        assert_eq!(found.paragraph, Some(4));
+        assert!(found.in_text_box && !found.in_table);
+        assert_eq!(found.context, "{{B}}");
+    }
+
+    #[test]
+    fn locates_token_outside_paragraphs() {
+        let xml = r#"<Relationships><Relationship Id="rId1" Target="{{URL}}"/></Relationships>"#;
+        let found = occurrence("word/_rels/footer2.xml.rels", xml, "{{URL}}");
+        assert_eq!(found.paragraph, None);
+        assert!(found.in_header);
+        assert_eq!(found.context, "{{URL}}");
+    }
+
+    #[test]
+    fn cuts_snippet_around_token() {
+        let text = ["č".repeat(50), " {{NAME}} ".to_string(), "ř".repeat(50)].concat();
+        assert_eq!(
+            snippet(&text, "{{NAME}}"),
+            [
+                ELLIPSIS,
+                &"č".repeat(25),
+                " {{NAME}} ",
+                &"ř".repeat(25),
+                ELLIPSIS
+            ]
+            .concat()
+        );
+
+        let text = ["ž".repeat(100), "{{A}}".to_string()].concat();
+        assert_eq!(
+            snippet(&text, "{{A}}"),
+            [ELLIPSIS, &"ž".repeat(55), "{{A}}"].concat()
+        );
+
+        let token = format!("{{{{{}}}}}", "Ř".repeat(66));
+        let text = ["a".repeat(10), token.clone(), "b".repeat(10)].concat();
+        assert_eq!(
+            snippet(&text, &token),
+            [ELLIPSIS, &token, ELLIPSIS].concat()
+        );
+
+        assert_eq!(snippet("  short {{A}} ", "{{A}}"), "short {{A}}");
+    }
+}
@@ -0,0 +1,246 @@
+use regex::Regex;
+use std::ops::Range;
+
+lazy_static! {
+    /// Matches paragraph start/end tags and text nodes of a WordprocessingML part.
+    static ref TEXT_NODES_RE: Regex =
+        Regex::new(r"(<w:p(?:\s[^>]*)?>)|(</w:p>)|(<w:t(?:\s[^>]*)?>)([^<]*)</w:t>").unwrap();
+    /// Matches the run content elements that are visible as (white)space in the text - breaks and tabs.
+    static ref BREAKS_RE: Regex = Regex::new(r"<w:(?:br|cr|tab)(?:\s[^>]*)?/>").unwrap();
+}
+
+/// Single text node (`<w:t>` element) of the document XML.
+#[derive(Debug, Clone)]
+pub struct TextNode {
+    /// sequential number of the (innermost) paragraph containing this node, if any.
+    pub paragraph: Option<usize>,
+    /// byte range of the `<w:t ...>` opening tag.
+    pub tag: Range<usize>,
+    /// byte range of the node's (XML escaped) text content.
+    pub content: Range<usize>,
+}
+
+/// Lists all text nodes of the XML document in their document order.
+pub fn text_nodes(xml: &str) -> Vec<TextNode> {
+    let mut nodes: Vec<TextNode> = Default::default();
+    let mut open_paragraphs: Vec<usize> = Default::default();
+    let mut paragraph_count: usize = 0;
+
+    for cap in TEXT_NODES_RE.captures_iter(xml) {
+        if cap.get(1).is_some() {
+            open_paragraphs.push(paragraph_count);
+            paragraph_count += 1;
+        } else if cap.get(2).is_some() {
+            open_paragraphs.pop();
+        } else if let (Some(tag), Some(content)) = (cap.get(3), cap.get(4)) {
+            nodes.push(TextNode {
+                paragraph: open_paragraphs.last().copied(),
+                tag: tag.range(),
+                content: content.range(),
+            });
+        }
+    }
+    nodes
+}
+
+/// Moves tokens whose text is split among several runs of a paragraph (Word does this with spell-checking,
+/// editing history, etc.) into the text node of the token's first run, so that each token becomes
+/// a contiguous piece of text again. The token keeps formatting of its first run this way.
+///
+/// # Arguments
+///
+/// * `xml` - contents of the WordprocessingML part (document, header, ...)
+/// * `token_re` - regular expression matching a single token
+pub fn merge_split_tokens(xml: &str, token_re: &Regex) -> String {
+    let nodes = text_nodes(xml);
+    let mut new_contents: Vec<Option<String>> = vec![None; nodes.len()];
+
+    let mut start = 0;
+    while start < nodes.len() {
+        let paragraph = nodes[start].paragraph;
+        let mut end = start + 1;
+        while end < nodes.len() && paragraph.is_some() && nodes[end].paragraph == paragraph {
+            end += 1;
+        }
+        merge_paragraph(
+            xml,
+            &nodes[start..end],
+            token_re,
+            &mut new_contents[start..end],
+        );
+        start = end;
+    }
+
+    let mut output = String::with_capacity(xml.len());
+    let mut last = 0;
+    for (node, content) in nodes.iter().zip(new_contents.iter()) {
+        if let Some(content) = content {
+            output.push_str(&xml[last..node.tag.start]);
+            output.push_str(&preserved_tag(&xml[node.tag.clone()], content));
+            output.push_str(content);
+            last = node.content.end;
+        }
+    }
+    output.push_str(&xml[last..]);
+    output
+}
+
+/// Returns the visible text of the XML fragment (e.g. paragraph), joined from all of its text nodes.
+pub fn visible_text(xml: &str) -> String {
+    text_nodes(xml)
+        .into_iter()
+        .map(|node| &xml[node.content])
+        .collect()
+}
+
+/// Removes all the visible text (text nodes content, breaks and tabs) from the specified byte range
+/// of the XML document. Markup of the range is kept, so the resulting XML is well formed.
+/// Returns the remaining contents of the range.
+pub fn strip_text(xml: &str, range: Range<usize>) -> String {
+    let mut output = String::with_capacity(range.len());
+    let mut last = range.start;
+    for node in text_nodes(xml) {
+        let start = node.content.start.max(range.start);
+        let end = node.content.end.min(range.end);
+        if start < end {
+            output.push_str(&xml[last..start]);
+            last = end;
+        }
+    }
+    output.push_str(&xml[last..range.end]);
+    BREAKS_RE.replace_all(&output, "").to_string()
+}
+
+/// Marks all the text nodes with leading or trailing whitespace (e.g. after filling in the values)
+/// to have their whitespace preserved, so that it does not get trimmed away by Word.
+pub fn preserve_spaces(xml: &str) -> String {
+    let mut output = String::with_capacity(xml.len());
+    let mut last = 0;
+    for node in text_nodes(xml) {
+        let tag = &xml[node.tag.clone()];
+        let new_tag = preserved_tag(tag, &xml[node.content]);
+        if new_tag != tag {
+            output.push_str(&xml[last..node.tag.start]);
+            output.push_str(&new_tag);
+            last = node.tag.end;
+        }
+    }
+    output.push_str(&xml[last..]);
+    output
+}
+
+/// Distributes visible text of one paragraph among its text nodes, keeping every token in a single node.
+/// Only the nodes whose text changes get their new content set.
+fn merge_paragraph(
+    xml: &str,
+    nodes: &[TextNode],
+    token_re: &Regex,
+    new_contents: &mut [Option<String>],
+) {
+    if nodes.len() < 2 {
+        return;
+    }
+
+    let mut text = String::new();
+    let mut owners: Vec<usize> = Default::default();
+    for (i, node) in nodes.iter().enumerate() {
+        let content = &xml[node.content.clone()];
+        text.push_str(content);
+        owners.resize(owners.len() + content.len(), i);
+    }
+
+    let mut changed = false;
+    for token in token_re.find_iter(&text) {
+        let first = owners[token.start()];
+        if owners[token.end() - 1] != first {
+            owners[token.range()]
+                .iter_mut()
+                .for_each(|owner| *owner = first);
+            changed = true;
+        }
+    }
+    if !changed {
+        return;
+    }
+
+    let mut contents: Vec<String> = vec![String::new(); nodes.len()];
+    for (i, ch) in text.char_indices() {
+        contents[owners[i]].push(ch);
+    }
+    for (i, content) in contents.into_iter().enumerate() {
+        if content != xml[nodes[i].content.clone()] {
+            new_contents[i] = Some(content);
+        }
+    }
+}
+
+/// Returns the `<w:t>` opening tag, with whitespace preservation added when the new content needs it.
+fn preserved_tag(tag: &str, content: &str) -> String {
+    let needs_preserve =
+        content.starts_with(char::is_whitespace) || content.ends_with(char::is_whitespace);
+    if needs_preserve && !tag.contains("xml:space") {
+        return tag.replacen("<w:t", r#"<w:t xml:space="preserve""#, 1);
+    }
+    tag.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn token_re() -> Regex {
+        Regex::new(r"\{\{[^{}]*\}\}").unwrap()
+    }
+
+    #[test]
+    fn merges_token_split_across_runs() {
+        let xml = "<w:p><w:r><w:t>Dear {{NA</w:t></w:r><w:r><w:rPr><w:b/></w:rPr><w:t>ME}}, hi</w:t></w:r></w:p>";
+        assert_eq!(
+            merge_split_tokens(xml, &token_re()),
+            "<w:p><w:r><w:t>Dear {{NAME}}</w:t></w:r><w:r><w:rPr><w:b/></w:rPr><w:t>, hi</w:t></w:r></w:p>"
+        );
+    }
+
+    #[test]
+    fn merges_token_split_across_many_runs() {
+        let xml =
+            "<w:p><w:r><w:t>{</w:t></w:r><w:r><w:t>{A</w:t></w:r><w:r><w:t>}}</w:t></w:r></w:p>";
+        assert_eq!(
+            merge_split_tokens(xml, &token_re()),
+            "<w:p><w:r><w:t>{{A}}</w:t></w:r><w:r><w:t></w:t></w:r><w:r><w:t></w:t></w:r></w:p>"
+        );
+    }
+
+    #[test]
+    fn keeps_tokens_of_single_runs_and_other_paragraphs() {
+        let xml = "<w:p><w:r><w:t>{{A}}</w:t></w:r><w:r><w:t>{{B</w:t></w:r></w:p><w:p><w:r><w:t>}}</w:t></w:r></w:p>";
+        assert_eq!(merge_split_tokens(xml, &token_re()), xml);
+    }
+
+    #[test]
+    fn lists_text_nodes_by_paragraph() {
+        let xml = "<w:p><w:r><w:t>a</w:t></w:r></w:p><w:p><w:r><w:t xml:space=\"preserve\"> b</w:t></w:r></w:p>";
+        let nodes = text_nodes(xml);
+        let paragraphs: Vec<Option<usize>> = nodes.iter().map(|node| node.paragraph).collect();
+        assert_eq!(paragraphs, vec![Some(0), Some(1)]);
+        assert_eq!(visible_text(xml), "a b");
+    }
+
+    #[test]
+    fn strips_text_keeping_markup() {
+        let xml = "<w:p><w:r><w:t>a</w:t><w:br/><w:t>b</w:t></w:r></w:p>";
+        assert_eq!(
+            strip_text(xml, 0..xml.len()),
+            "<w:p><w:r><w:t></w:t><w:t></w:t></w:r></w:p>"
+        );
+    }
+
+    #[test]
+    fn preserves_surrounding_spaces() {
+        let xml = "<w:t>a </w:t><w:t>b</w:t><w:t xml:space=\"preserve\"> c</w:t>";
+        assert_eq!(
+            preserve_spaces(xml),
+            "<w:t xml:space=\"preserve\">a </w:t><w:t>b</w:t><w:t xml:space=\"preserve\"> c</w:t>"
+        );
+    }
+}
@@ -0,0 +1,169 @@
+use super::{DocxError, DocxResult};
+use crate::lang;
+use std::fs::{self, File};
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use tempfile::TempPath;
+
+/// Prefix of the temporary files the generated documents are written into, before they are moved into place.
+const STAGING_PREFIX: &str = ".docx-filler-";
+/// Extension of the temporary files the generated documents are written into.
+const STAGING_SUFFIX: &str = ".tmp";
+
+/// Generated file written into a temporary file in the directory of its final path, waiting to be moved into place.
+/// Temporary file is removed, unless the staged file is committed.
+#[derive(Debug)]
+pub struct StagedFile {
+    /// temporary file with the whole contents of the generated file.
+    temp: TempPath,
+    /// final path of the generated file.
+    path: PathBuf,
+}
+
+impl StagedFile {
+    /// Writes the generated file into a temporary file in the directory of its final path.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - final path of the generated file
+    /// * `write` - function writing the contents of the generated file
+    ///
+    /// # Errors
+    ///
+    /// Can return I/O errors if the temporary file cannot be written, or the error of the `write` function.
+    pub fn write(
+        path: &Path,
+        write: impl FnOnce(&mut File) -> DocxResult<()>,
+    ) -> DocxResult<StagedFile> {
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        let mut file = tempfile::Builder::new()
+            .prefix(STAGING_PREFIX)
+            .suffix(STAGING_SUFFIX)
+            .tempfile_in(dir)?;
+        write(file.as_file_mut())?;
+        file.as_file().sync_all()?;
+        Ok(StagedFile {
+            temp: file.into_temp_path(),
+            path: path.to_path_buf(),
+        })
+    }
+
+    /// Moves the staged file into its final path (atomically, so the file is either complete, or not there at all).
+    ///
+    /// # Errors
+    ///
+    /// Can return Docx::Processing if the file already exists, or I/O error if it cannot be moved.
+    pub fn commit(self) -> DocxResult<()> {
+        let path = self.path;
+        self.temp
+            .persist_noclobber(&path)
+            .map_err(|err| match err.error.kind() {
+                ErrorKind::AlreadyExists => overwrite_error(&path.to_string_lossy()),
+                _ => DocxError::Io(err.error),
+            })
+    }
+}
+
+/// Moves all the staged files into their final paths, in their order. If some of them cannot be moved,
+/// the ones moved already are removed again (and the rest of them is not moved at all).
+///
+/// # Errors
+///
+/// Can return the error of the first staged file that cannot be moved.
+pub fn commit_all(staged: Vec<StagedFile>) -> DocxResult<()> {
+    let mut committed: Vec<PathBuf> = Default::default();
+    for file in staged {
+        let path = file.path.clone();
+        if let Err(err) = file.commit() {
+            for path in committed.iter() {
+                let _ = fs::remove_file(path);
+            }
+            return Err(err);
+        }
+        committed.push(path);
+    }
+    Ok(())
+}
+
+/// Creates error for the generated file, that already exists.
+pub fn overwrite_error(filename: &str) -> DocxError {
+    let args: lang::TrArgVec = vec![("filename".to_string(), filename.to_string())];
+    let msg = lang::tr_with_args("docx-filler-fail-overwrite", &args);
+    DocxError::Processing(msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn stage(path: &Path, text: &str) -> StagedFile {
+        StagedFile::write(path, |file| Ok(file.write_all(text.as_bytes())?)).unwrap()
+    }
+
+    fn dir_entries(dir: &Path) -> usize {
+        fs::read_dir(dir).unwrap().count()
+    }
+
+    #[test]
+    fn commits_staged_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a.docx");
+        let staged = stage(&path, "a");
+        assert!(!path.exists());
+        staged.commit().unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "a");
+        assert_eq!(dir_entries(dir.path()), 1);
+    }
+
+    #[test]
+    fn removes_uncommitted_file() {
+        let dir = tempfile::tempdir().unwrap();
+        drop(stage(&dir.path().join("a.docx"), "a"));
+        assert_eq!(dir_entries(dir.path()), 0);
+    }
+
+    #[test]
+    fn refuses_to_overwrite_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a.docx");
+        fs::write(&path, "old").unwrap();
+        assert!(matches!(
+            stage(&path, "new").commit(),
+            Err(DocxError::Processing(_))
+        ));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "old");
+        assert_eq!(dir_entries(dir.path()), 1);
+    }
+
+    #[test]
+    fn commits_all_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let paths: Vec<PathBuf> = ["a.docx", "b.docx"]
+            .iter()
+            .map(|name| dir.path().join(name))
+            .collect();
+        let staged = paths.iter().map(|path| stage(path, "x")).collect();
+        commit_all(staged).unwrap();
+        assert!(paths.iter().all(|path| path.exists()));
+    }
+
+    #[test]
+    fn rolls_back_all_files_on_failure() {
+        let dir = tempfile::tempdir().unwrap();
+        let paths: Vec<PathBuf> = ["a.docx", "b.docx", "c.docx"]
+            .iter()
+            .map(|name| dir.path().join(name))
+            .collect();
+        fs::write(&paths[1], "old").unwrap();
+        let staged = paths.iter().map(|path| stage(path, "new")).collect();
+        assert!(commit_all(staged).is_err());
+        assert!(!paths[0].exists());
+        assert_eq!(fs::read_to_string(&paths[1]).unwrap(), "old");
+        assert!(!paths[2].exists());
+        assert_eq!(dir_entries(dir.path()), 1);
+    }
+}
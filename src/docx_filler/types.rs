@@ -0,0 +1,132 @@
+use super::formats;
+use regex::Regex;
+
+/// Separator of the token name and its type, e.g. `{{AGE:int}}`.
+const TYPE_SEPARATOR: char = ':';
+
+lazy_static! {
+    /// Matches the decimal number value, with decimal point or comma (whitespace removed beforehand).
+    static ref DECIMAL_RE: Regex = Regex::new(r"^[+-]?\d+(?:[.,]\d+)?$").unwrap();
+    /// Matches the e-mail address value (just its basic shape, `name@domain.tld`).
+    static ref EMAIL_RE: Regex = Regex::new(r"^[^@\s]+@[^@\s]+\.[^@\s.]+$").unwrap();
+}
+
+/// Type of the token value declared in the template, e.g. `int` for `{{AGE:int}}`.
+/// Values not matching the type are rejected before any document is generated.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TokenType {
+    /// whole number, e.g. `42`.
+    Int,
+    /// decimal number, with decimal point or comma, e.g. `1234,50`.
+    Decimal,
+    /// existing date in one of the accepted formats, e.g. `2022-04-01` or `1.4.2022`.
+    Date,
+    /// e-mail address, e.g. `joseph@example.com`.
+    Email,
+}
+
+impl TokenType {
+    /// Returns the type of the given name, if there is such type.
+    fn from_name(name: &str) -> Option<TokenType> {
+        match name {
+            "int" => Some(TokenType::Int),
+            "decimal" => Some(TokenType::Decimal),
+            "date" => Some(TokenType::Date),
+            "email" => Some(TokenType::Email),
+            _ => None,
+        }
+    }
+
+    /// Returns the name of the type, as written in the template.
+    pub fn name(&self) -> &'static str {
+        match self {
+            TokenType::Int => "int",
+            TokenType::Decimal => "decimal",
+            TokenType::Date => "date",
+            TokenType::Email => "email",
+        }
+    }
+
+    /// Checks whether the value matches the type. Empty value is accepted for any type.
+    pub fn accepts(&self, value: &str) -> bool {
+        let value = value.trim();
+        if value.is_empty() {
+            return true;
+        }
+        match self {
+            TokenType::Int => value.parse::<i64>().is_ok(),
+            TokenType::Decimal => DECIMAL_RE.is_match(&value.replace(char::is_whitespace, "")),
+            TokenType::Date => formats::parse_date(value).is_some(),
+            TokenType::Email => EMAIL_RE.is_match(value),
+        }
+    }
+}
+
+/// Splits the token name into the name itself and its declared type (e.g. `AGE` and `int` for `AGE:int`).
+/// Text after the separator that is not a known type stays a part of the name (e.g. `#index:04`).
+pub fn split_type(name: &str) -> (&str, Option<TokenType>) {
+    match name.rsplit_once(TYPE_SEPARATOR) {
+        Some((base, kind)) => match TokenType::from_name(kind.trim()) {
+            Some(kind) => (base, Some(kind)),
+            None => (name, None),
+        },
+        None => (name, None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::delimiters::Delimiters;
+    use super::*;
+
+    #[test]
+    fn splits_declared_type() {
+        assert_eq!(split_type("AGE:int"), ("AGE", Some(TokenType::Int)));
+        assert_eq!(split_type("SENT: date "), ("SENT", Some(TokenType::Date)));
+        assert_eq!(split_type("#index:04"), ("#index:04", None));
+        assert_eq!(split_type("NAME"), ("NAME", None));
+    }
+
+    #[test]
+    fn parses_type_along_with_default() {
+        let parts = formats::split_token("{{AGE:int?=18}}", &Delimiters::default());
+        assert_eq!(parts.token, "{{AGE}}");
+        assert_eq!(parts.kind, Some(TokenType::Int));
+        assert_eq!(parts.default.as_deref(), Some("18"));
+        assert!(TokenType::Int.accepts(&parts.fill("")));
+    }
+
+    #[test]
+    fn accepts_values_of_type() {
+        let cases = [
+            (
+                TokenType::Int,
+                vec!["42", " -7 ", ""],
+                vec!["abc", "4.2", "1 000"],
+            ),
+            (
+                TokenType::Decimal,
+                vec!["42", "1234,50", "-0.5", "1 234.5"],
+                vec!["1,2,3", "12.", "x1"],
+            ),
+            (
+                TokenType::Date,
+                vec!["2022-04-01", "1.4.2022", "1. 4. 2022", "2022/04/01"],
+                vec!["2022-02-30", "April 1", "1.4."],
+            ),
+            (
+                TokenType::Email,
+                vec!["joseph@example.com", "a.b@c.d.org"],
+                vec!["joseph", "a@b", "a b@c.d", "a@@b.c"],
+            ),
+        ];
+        for (kind, accepted, rejected) in cases.iter() {
+            for value in accepted {
+                assert!(kind.accepts(value), "{} {}", kind.name(), value);
+            }
+            for value in rejected {
+                assert!(!kind.accepts(value), "{} {}", kind.name(), value);
+            }
+        }
+    }
+}
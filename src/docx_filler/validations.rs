@@ -1,4 +1,8 @@
-use super::{DocxError, TokenPackArg, ValuePack, ValuePackArg};
+use super::builtins::Builtins;
+use super::{
+    BatchOutput, DefaultPackArg, DocxError, LineFormat, TokenPackArg, TypePackArg, Value,
+    ValuePack, ValuePackArg,
+};
 use crate::lang;
 use std::collections::HashMap;
 
@@ -8,6 +12,9 @@ use std::collections::HashMap;
 ///
 /// * `tokens` - vector of tokens to be replaced
 /// * `values` - vector of values to be filled in place of tokens
+/// * `defaults` - default values of tokens declared in the template
+/// * `types` - types of values of tokens declared in the template
+/// * `builtins` - built-in tokens used in the template and output pattern
 /// * `output_pattern` - output file pattern (explicit string or pattern contains tokens)
 ///
 /// # Errors
@@ -16,11 +23,17 @@ use std::collections::HashMap;
 pub fn validate_single(
     tokens: TokenPackArg,
     values: ValuePackArg,
+    defaults: DefaultPackArg,
+    types: TypePackArg,
+    builtins: &Builtins,
     output_pattern: &str,
 ) -> Result<(), DocxError> {
     validate_tokens(tokens)?;
-    validate_values(tokens, values)?;
-    let filename = super::replace_tokens(output_pattern, tokens, values);
+    validate_values(tokens, values, defaults)?;
+    let values = super::with_defaults(values, defaults);
+    validate_types(tokens, &values, types)?;
+    let (tokens, values) = builtins.complete(tokens, &values, 1);
+    let filename = super::replace_tokens(output_pattern, &tokens, &values);
     validate_filename(&filename)?;
     Ok(())
 }
@@ -31,21 +44,39 @@ pub fn validate_single(
 /// # Arguments
 ///
 /// * `tokens` - vector of tokens to be verified
+/// * `defaults` - default values of tokens declared in the template
+/// * `types` - types of values of tokens declared in the template
+/// * `builtins` - built-in tokens used in the template and output pattern
 /// * `text` - one to many lines of text - a set of values per each line for a new document to be generated
+/// * `format` - format of each line of input - value separator, and which of the values are lists
 /// * `output_pattern` - output file pattern (pattern containing tokens)
+/// * `output` - output of the batch - separate documents (with distinct filenames), or a single combined one
 ///
 /// # Errors
 ///
 /// Can return Docx::Validation on failure, with details in message.
+#[allow(clippy::too_many_arguments)]
 pub fn validate_batch(
     tokens: TokenPackArg,
+    defaults: DefaultPackArg,
+    types: TypePackArg,
+    builtins: &Builtins,
     text: &str,
-    separator: &str,
+    format: &LineFormat,
     output_pattern: &str,
+    output: BatchOutput,
 ) -> Result<(), DocxError> {
     validate_tokens(tokens)?;
-    validate_values_multiline(text, separator, tokens)?;
-    validate_filename_multiline(tokens, text, separator, output_pattern)?;
+    validate_values_multiline(text, format, tokens, defaults, types)?;
+    validate_filename_multiline(
+        tokens,
+        defaults,
+        builtins,
+        text,
+        format,
+        output_pattern,
+        output,
+    )?;
     Ok(())
 }
 
@@ -84,32 +115,41 @@ fn tokens_counts_map(tokens: TokenPackArg) -> HashMap<String, u8> {
 
 /// Verifies output file-name pattern. Checks for potential duplicities in filenames of whole input data set
 /// (embedding the token values into output pattern, if such pattern used).
+/// Combined batch output has a single filename, so the duplicities are allowed.
 ///
 /// # Arguments
 ///
 /// * `tokens` - vector of tokens to be replaced
+/// * `defaults` - default values of tokens declared in the template
+/// * `builtins` - built-in tokens used in the template and output pattern
 /// * `text` - one to many lines of text - a set of values per each line for a new document to be generated
-/// * `separator` - string to be used as a value separator on each line of input
+/// * `format` - format of each line of input - value separator, and which of the values are lists
 /// * `output_pattern` - output file pattern (explicit string or pattern contains tokens)
+/// * `output` - output of the batch - separate documents, or a single combined one
 ///
 /// # Errors
 ///
 /// Can return Docx::Validation on failure, with details in message.
 fn validate_filename_multiline(
     tokens: TokenPackArg,
+    defaults: DefaultPackArg,
+    builtins: &Builtins,
     text: &str,
-    separator: &str,
+    format: &LineFormat,
     output_pattern: &str,
+    output: BatchOutput,
 ) -> Result<(), DocxError> {
     let mut names: HashMap<String, bool> = Default::default();
-    for line in text.lines() {
-        let values = super::string_to_values(line, separator);
-        validate_values(tokens, &values)?;
+    for (i, line) in text.lines().enumerate() {
+        let values = super::string_to_values(line, format);
+        validate_values(tokens, &values, defaults)?;
 
-        let filename = super::replace_tokens(output_pattern, tokens, &values);
+        let values = super::with_defaults(&values, defaults);
+        let (tokens, values) = builtins.complete(tokens, &values, i + 1);
+        let filename = super::replace_tokens(output_pattern, &tokens, &values);
         validate_filename(&filename)?;
 
-        if names.contains_key(&filename) {
+        if output == BatchOutput::Separate && names.contains_key(&filename) {
             let args: lang::TrArgVec = vec![("filename".to_string(), filename)];
             let msg = lang::tr_with_args("valid-same-output-filename", &args);
             return Err(DocxError::Validation(msg));
@@ -121,12 +161,21 @@ fn validate_filename_multiline(
 }
 
 /// Validates the consistency of input sets of tokens and values.
-fn validate_values(tokens: TokenPackArg, values: ValuePackArg) -> Result<(), DocxError> {
+/// Values of the trailing tokens with default values can be omitted.
+fn validate_values(
+    tokens: TokenPackArg,
+    values: ValuePackArg,
+    defaults: DefaultPackArg,
+) -> Result<(), DocxError> {
     if values.is_empty() {
         return Err(DocxError::Validation(lang::tr("valid-missing-input")));
     }
 
-    if values.len() != tokens.len() {
+    let required = defaults
+        .iter()
+        .rposition(Option::is_none)
+        .map_or(0, |i| i + 1);
+    if values.len() < required || values.len() > tokens.len() {
         let args: lang::TrArgVec = vec![
             ("tokens".to_string(), tokens.len().to_string()),
             ("values".to_string(), values.len().to_string()),
@@ -138,6 +187,33 @@ fn validate_values(tokens: TokenPackArg, values: ValuePackArg) -> Result<(), Doc
     Ok(())
 }
 
+/// Validates the values of the tokens with declared type (e.g. `{{AGE:int}}`) - whether they match the type.
+/// Empty values are accepted, as well as the list values.
+///
+/// # Errors
+///
+/// Can return Docx::Validation on failure, with details in message.
+fn validate_types(
+    tokens: TokenPackArg,
+    values: ValuePackArg,
+    types: TypePackArg,
+) -> Result<(), DocxError> {
+    for ((token, value), kind) in tokens.iter().zip(values).zip(types) {
+        if let (Some(kind), Value::Text(text)) = (kind, value) {
+            if !kind.accepts(text) {
+                let args: lang::TrArgVec = vec![
+                    ("token".to_string(), token.to_string()),
+                    ("value".to_string(), text.to_string()),
+                    ("type".to_string(), kind.name().to_string()),
+                ];
+                let msg = lang::tr_with_args("valid-type-mismatch", &args);
+                return Err(DocxError::Validation(msg));
+            }
+        }
+    }
+    Ok(())
+}
+
 /// Verifies string to be used as a filename for generated output - whether it has .docx extension.
 ///
 /// # Errors
@@ -152,23 +228,27 @@ fn validate_filename(filename: &str) -> Result<(), DocxError> {
     Ok(())
 }
 
-/// Verifies number of values is equal to number of tokens for each line of the input text.
+/// Verifies number of values is equal to number of tokens for each line of the input text,
+/// and that the values match the types declared for their tokens.
 ///
 /// # Errors
 ///
 /// Can return Docx::Validation on failure, with details in message.
 fn validate_values_multiline(
     text: &str,
-    separator: &str,
+    format: &LineFormat,
     tokens: TokenPackArg,
+    defaults: DefaultPackArg,
+    types: TypePackArg,
 ) -> Result<(), DocxError> {
-    let mut i: usize = 1;
     if text.is_empty() {
         return Err(DocxError::Validation(lang::tr("valid-missing-input")));
     }
-    for inst in text.lines() {
-        let values: ValuePack = super::string_to_values(inst, separator);
-        if let Err(err) = validate_values(tokens, &values) {
+    for (i, inst) in (1..).zip(text.lines()) {
+        let values: ValuePack = super::string_to_values(inst, format);
+        let validated = validate_values(tokens, &values, defaults)
+            .and_then(|_| validate_types(tokens, &super::with_defaults(&values, defaults), types));
+        if let Err(err) = validated {
             let args: lang::TrArgVec = vec![
                 ("line".to_string(), i.to_string()),
                 ("details".to_string(), err.to_string()),
@@ -176,7 +256,88 @@ fn validate_values_multiline(
             let msg = lang::tr_with_args("valid-line-mismatch", &args);
             return Err(DocxError::Validation(msg));
         };
-        i += 1;
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::super::delimiters::Delimiters;
+    use super::super::types::TokenType;
+    use super::*;
+    use std::path::Path;
+
+    fn tokens() -> Vec<String> {
+        vec!["{{NAME}}".to_string(), "{{AGE}}".to_string()]
+    }
+
+    fn validate(text: &str, defaults: DefaultPackArg) -> Result<(), DocxError> {
+        let format = LineFormat {
+            separator: ";".to_string(),
+            lists: vec![false, false],
+        };
+        let builtins = Builtins::new(
+            Default::default(),
+            Path::new("t.docx"),
+            &Delimiters::default(),
+        );
+        validate_batch(
+            &tokens(),
+            defaults,
+            &[None, Some(TokenType::Int)],
+            &builtins,
+            text,
+            &format,
+            "{{NAME}}.docx",
+            BatchOutput::Separate,
+        )
+    }
+
+    fn mismatch(line: usize, value: &str) -> String {
+        let args: lang::TrArgVec = vec![
+            ("token".to_string(), "{{AGE}}".to_string()),
+            ("value".to_string(), value.to_string()),
+            ("type".to_string(), "int".to_string()),
+        ];
+        let args: lang::TrArgVec = vec![
+            ("line".to_string(), line.to_string()),
+            (
+                "details".to_string(),
+                DocxError::Validation(lang::tr_with_args("valid-type-mismatch", &args)).to_string(),
+            ),
+        ];
+        lang::tr_with_args("valid-line-mismatch", &args)
+    }
+
+    #[test]
+    fn rejects_values_not_matching_type() {
+        let types = [None, Some(TokenType::Int)];
+        let values = [Value::from("Ann"), Value::from("abc")];
+        assert!(validate_types(&tokens(), &values, &types).is_err());
+        let values = [Value::from("Ann"), Value::from("42")];
+        assert!(validate_types(&tokens(), &values, &types).is_ok());
+        let values = [Value::from("Ann"), Value::List(vec![])];
+        assert!(validate_types(&tokens(), &values, &types).is_ok());
+    }
+
+    #[test]
+    fn reports_line_of_value_not_matching_type() {
+        assert!(validate("Ann; 30\nBob; 40", &[]).is_ok());
+        match validate("Ann; 30\nBob; abc", &[]) {
+            Err(DocxError::Validation(msg)) => assert_eq!(msg, mismatch(2, "abc")),
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn validates_default_values_by_type() {
+        let defaults = [None, Some("18".to_string())];
+        assert!(validate("Ann\nBob;", &defaults).is_ok());
+        let defaults = [None, Some("adult".to_string())];
+        assert!(validate("Ann; 30", &defaults).is_ok());
+        match validate("Ann; 30\nBob", &defaults) {
+            Err(DocxError::Validation(msg)) => assert_eq!(msg, mismatch(2, "adult")),
+            other => panic!("{:?}", other),
+        }
+    }
+}
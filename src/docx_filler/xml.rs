@@ -0,0 +1,274 @@
+use regex::Regex;
+use std::collections::HashMap;
+use std::ops::Range;
+use std::sync::Mutex;
+
+lazy_static! {
+    /// Matches value of the `Target` attribute of a relationship.
+    static ref TARGET_RE: Regex = Regex::new(r#"\sTarget="([^"]*)""#).unwrap();
+    /// Matches the text content of an element (between its tags).
+    static ref TEXT_RE: Regex = Regex::new(r">([^<]+)<").unwrap();
+    /// Matches any element tag - opening, closing or self-closing one (XML declaration excluded).
+    static ref TAG_RE: Regex = Regex::new(r"<(/?)([\w:.-]+)[^>]*?(/?)>").unwrap();
+    /// Regexes matching the tags of elements with the given name, compiled on their first use.
+    static ref ELEMENT_RES: Mutex<HashMap<String, Regex>> = Default::default();
+}
+
+/// XML context of a place in the document, determining how the text put there has to be escaped.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum XmlContext {
+    /// content of an element.
+    Text,
+    /// text of the `<w:t>` node of a run, where line breaks and tabs are separate elements.
+    RunText,
+    /// value of an element attribute.
+    Attribute,
+    /// target (URI) of a relationship, e.g. address of a hyperlink.
+    Target,
+}
+
+/// Determines the XML context of the specified position (byte offset) in the XML document.
+pub fn context_at(xml: &str, pos: usize) -> XmlContext {
+    let before = &xml[..pos];
+    let tag_start = before.rfind('<');
+    let tag_end = before.rfind('>');
+    match (tag_start, tag_end) {
+        (Some(start), Some(end)) if start < end => {
+            let tag = &before[start..=end];
+            if tag.starts_with("<w:t>") || tag.starts_with("<w:t ") {
+                XmlContext::RunText
+            } else {
+                XmlContext::Text
+            }
+        }
+        (Some(start), _) => {
+            let tag = &before[start..];
+            match tag.rfind('=') {
+                Some(eq) if tag[..eq].trim_end().ends_with(" Target") => XmlContext::Target,
+                _ => XmlContext::Attribute,
+            }
+        }
+        (None, _) => XmlContext::Text,
+    }
+}
+
+/// Escapes the plain text value, so it can be safely placed into the specified XML context.
+/// Line breaks and tabs within the run text are turned into `<w:br/>` and `<w:tab/>` elements of the run.
+pub fn escape(value: &str, context: XmlContext) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for ch in value.chars() {
+        match (ch, context) {
+            ('&', _) => escaped.push_str("&amp;"),
+            ('<', XmlContext::Target) => escaped.push_str("%3C"),
+            ('>', XmlContext::Target) => escaped.push_str("%3E"),
+            ('<', _) => escaped.push_str("&lt;"),
+            ('>', _) => escaped.push_str("&gt;"),
+            ('\r', XmlContext::RunText) => {}
+            ('\n', XmlContext::RunText) => escaped.push_str("</w:t><w:br/><w:t>"),
+            ('\t', XmlContext::RunText) => escaped.push_str("</w:t><w:tab/><w:t>"),
+            ('"', XmlContext::Attribute) => escaped.push_str("&quot;"),
+            ('\'', XmlContext::Attribute) => escaped.push_str("&apos;"),
+            (ch, XmlContext::Target) if is_unsafe_in_uri(ch) => {
+                let mut buffer = [0; 4];
+                for byte in ch.encode_utf8(&mut buffer).bytes() {
+                    escaped.push_str(&format!("%{:02X}", byte));
+                }
+            }
+            (ch, _) => escaped.push(ch),
+        }
+    }
+    escaped
+}
+
+/// Turns the escape sequences of the value into the characters they stand for - `\n` into a line break,
+/// `\t` into a tab and `\\` into a backslash, so that they can be typed within a single line of values.
+/// Backslashes followed by anything else are kept as they are.
+pub fn unescape_sequences(value: &str) -> String {
+    let mut unescaped = String::with_capacity(value.len());
+    let mut chars = value.chars().peekable();
+    while let Some(ch) = chars.next() {
+        let sequence = match (ch, chars.peek()) {
+            ('\\', Some('n')) => '\n',
+            ('\\', Some('t')) => '\t',
+            ('\\', Some('\\')) => '\\',
+            (ch, _) => {
+                unescaped.push(ch);
+                continue;
+            }
+        };
+        chars.next();
+        unescaped.push(sequence);
+    }
+    unescaped
+}
+
+/// Turns the XML escaped text (e.g. text node content) back into the plain text.
+pub fn unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Checks whether the character has to be percent-encoded when used within URI.
+fn is_unsafe_in_uri(ch: char) -> bool {
+    ch.is_ascii_control() || " \"<>\\^`{|}".contains(ch)
+}
+
+/// Lists byte ranges of all relationship targets in the relationships XML part.
+pub fn target_ranges(xml: &str) -> Vec<Range<usize>> {
+    TARGET_RE
+        .captures_iter(xml)
+        .filter_map(|cap| cap.get(1))
+        .map(|target| target.range())
+        .collect()
+}
+
+/// Lists byte ranges of the text content of all the elements with text (e.g. values of document properties).
+pub fn text_ranges(xml: &str) -> Vec<Range<usize>> {
+    TEXT_RE
+        .captures_iter(xml)
+        .filter_map(|cap| cap.get(1))
+        .map(|text| text.range())
+        .collect()
+}
+
+/// Lists byte ranges of all the elements with the specified tag name (e.g. `w:p`), in order of their start.
+/// Nested elements of the same name are listed as well.
+pub fn element_ranges(xml: &str, tag: &str) -> Vec<Range<usize>> {
+    let re = element_regex(tag);
+    let mut ranges: Vec<Range<usize>> = Default::default();
+    let mut open: Vec<usize> = Default::default();
+    for found in re.find_iter(xml) {
+        let found_tag = found.as_str();
+        if found_tag.starts_with("</") {
+            if let Some(start) = open.pop() {
+                ranges.push(start..found.end());
+            }
+        } else if found_tag.ends_with("/>") {
+            ranges.push(found.range());
+        } else {
+            open.push(found.start());
+        }
+    }
+    ranges.sort_by_key(|range| range.start);
+    ranges
+}
+
+/// Returns the regex matching opening, closing and self-closing tags of elements with the specified tag name.
+fn element_regex(tag: &str) -> Regex {
+    let mut cache = ELEMENT_RES
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    cache
+        .entry(tag.to_string())
+        .or_insert_with(|| {
+            Regex::new(&format!(r"<{0}(?:\s[^>]*)?/?>|</{0}>", regex::escape(tag))).unwrap()
+        })
+        .clone()
+}
+
+/// Finds the innermost element with the specified tag name, that contains the given byte range.
+pub fn enclosing_element(xml: &str, inner: &Range<usize>, tag: &str) -> Option<Range<usize>> {
+    element_ranges(xml, tag)
+        .into_iter()
+        .rev()
+        .find(|range| range.start <= inner.start && inner.end <= range.end)
+}
+
+/// Checks whether all the elements opened in the XML fragment are also closed in it, and vice versa.
+pub fn is_balanced(fragment: &str) -> bool {
+    let mut open: Vec<&str> = Default::default();
+    for cap in TAG_RE.captures_iter(fragment) {
+        let (closing, name, self_closing) = (&cap[1], cap.get(2).unwrap().as_str(), &cap[3]);
+        if !closing.is_empty() {
+            if open.pop() != Some(name) {
+                return false;
+            }
+        } else if self_closing.is_empty() {
+            open.push(name);
+        }
+    }
+    open.is_empty()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_context() {
+        let xml =
+            r#"<w:p><w:r><w:t>A</w:t></w:r><w:instrText>B</w:instrText><w:x w:val="C"/></w:p>"#;
+        assert_eq!(context_at(xml, xml.find('A').unwrap()), XmlContext::RunText);
+        assert_eq!(context_at(xml, xml.find('B').unwrap()), XmlContext::Text);
+        assert_eq!(
+            context_at(xml, xml.find('C').unwrap()),
+            XmlContext::Attribute
+        );
+        let rels = r#"<Relationship Id="rId1" Target="D"/>"#;
+        assert_eq!(
+            context_at(rels, rels.find('D').unwrap()),
+            XmlContext::Target
+        );
+    }
+
+    #[test]
+    fn escapes_by_context() {
+        let value = "a<b & \"c\"\td\r\ne";
+        assert_eq!(
+            escape(value, XmlContext::RunText),
+            "a&lt;b &amp; \"c\"</w:t><w:tab/><w:t>d</w:t><w:br/><w:t>e"
+        );
+        assert_eq!(
+            escape(value, XmlContext::Attribute),
+            "a&lt;b &amp; &quot;c&quot;\td\r\ne"
+        );
+        assert_eq!(
+            escape("http://x.org/a b<č>", XmlContext::Target),
+            "http://x.org/a%20b%3Cč%3E"
+        );
+    }
+
+    #[test]
+    fn unescapes_sequences() {
+        assert_eq!(unescape_sequences(r"a\nb\tc\\n C:\x"), "a\nb\tc\\n C:\\x");
+    }
+
+    #[test]
+    fn unescapes_escaped_text() {
+        let value = "<'a' & \"b\">";
+        assert_eq!(unescape(&escape(value, XmlContext::Attribute)), value);
+    }
+
+    #[test]
+    fn lists_nested_and_self_closing_elements() {
+        let xml = "<w:p><w:pPr/><w:p w:x=\"1\"></w:p></w:p><w:p/>";
+        let ranges = element_ranges(xml, "w:p");
+        let elements: Vec<&str> = ranges.iter().map(|range| &xml[range.clone()]).collect();
+        assert_eq!(
+            elements,
+            vec![
+                "<w:p><w:pPr/><w:p w:x=\"1\"></w:p></w:p>",
+                "<w:p w:x=\"1\"></w:p>",
+                "<w:p/>"
+            ]
+        );
+    }
+
+    #[test]
+    fn finds_innermost_enclosing_element() {
+        let xml = "<w:tbl><w:tc><w:tbl><w:tc>X</w:tc></w:tbl></w:tc></w:tbl>";
+        let pos = xml.find('X').unwrap();
+        let table = enclosing_element(xml, &(pos..pos + 1), "w:tbl").unwrap();
+        assert_eq!(&xml[table], "<w:tbl><w:tc>X</w:tc></w:tbl>");
+    }
+
+    #[test]
+    fn checks_balance() {
+        assert!(is_balanced("<w:r><w:t>a</w:t><w:br/></w:r>"));
+        assert!(!is_balanced("</w:t></w:r><w:r><w:t>"));
+        assert!(!is_balanced("<w:r><w:t>a</w:r></w:t>"));
+    }
+}
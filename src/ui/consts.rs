@@ -10,12 +10,6 @@ pub const INPUT_HEIGHT: Dimension = Dimension::Points(LINE_HEIGHT);
 /// Common height for buttons across the app UI.
 pub const BUTTON_HEIGHT: Dimension = Dimension::Points(1.5 * LINE_HEIGHT);
 
-/// Common size to unify controls that are "one line of UI elements" tall.
-pub const MIN_ONELINER_SIZE: Size<Dimension> = Size {
-    width: Dimension::Percent(1.0),
-    height: Dimension::Points(2.0 * LINE_HEIGHT),
-};
-
 /// Common size to unify controls that are "two lines of UI elements" tall.
 pub const MIN_TWOLINER_SIZE: Size<Dimension> = Size {
     width: Dimension::Percent(1.0),
@@ -5,7 +5,7 @@ mod template_ui;
 mod tokens_ui;
 mod values_ui;
 
-use crate::docx_filler::DocxTemplate;
+use crate::docx_filler::{self, DocxTemplate, LintIssue, LintKind, TokenReport};
 use crate::lang;
 use crate::ui::{
     options_ui::OptionsUi, output_ui::OutputUi, template_ui::TemplateUi, tokens_ui::TokensUi,
@@ -45,7 +45,7 @@ pub struct FillerApp {
 
     // // configuration options controls
     #[nwg_control(flags: "VISIBLE")]
-    #[nwg_layout_item(layout: main_layout, flex_shrink: 1.0, min_size: consts::MIN_ONELINER_SIZE)]
+    #[nwg_layout_item(layout: main_layout, flex_shrink: 1.0, min_size: consts::MIN_TWOLINER_SIZE)]
     options_frame: nwg::Frame,
     #[nwg_partial(parent: options_frame)]
     #[nwg_events((lang_dropdown, OnComboxBoxSelection): [FillerApp::set_lang(SELF)])]
@@ -56,7 +56,7 @@ pub struct FillerApp {
     #[nwg_layout_item(layout: main_layout, flex_shrink: 1.0, min_size: consts::MIN_TWOLINER_SIZE)]
     template_frame: nwg::Frame,
     #[nwg_partial(parent: template_frame)]
-    #[nwg_events((button, OnButtonClick): [FillerApp::open_new_file(SELF)])]
+    #[nwg_events((button, OnButtonClick): [FillerApp::open_new_file(SELF)], (report_button, OnButtonClick): [FillerApp::show_token_report(SELF)])]
     template_partial: TemplateUi,
 
     // template tokens - list all the tokens found in opened docx, and allow changing order (by rather limited win32 lib UI controls)
@@ -109,10 +109,11 @@ impl FillerApp {
     ///  Updates all the app sub-components with new DOCX info as needed.
     fn load_docx(&self, file: &str) {
         let docx_path = Path::new(file);
-        match DocxTemplate::open(docx_path) {
+        let options = self.options_partial.get_template_options();
+        match DocxTemplate::open_with_options(docx_path, &options) {
             Ok(docx) => {
                 let tokens = match docx.template_tokens() {
-                    Ok(tokens) => tokens,
+                    Ok(tokens) => docx_filler::token_names(&tokens),
                     Err(err) => {
                         let err_msg = self.failed_load_str();
                         nwg::modal_error_message(&self.window, &err_msg, &err.to_string());
@@ -120,6 +121,7 @@ impl FillerApp {
                     }
                 };
 
+                let issues = docx.lint();
                 self.opened_docx.replace(Some(docx));
 
                 self.template_partial.set_current_docx(file);
@@ -133,6 +135,11 @@ impl FillerApp {
 
                 let output_pattern = format!("{}.docx", &tokens[0]);
                 self.output_partial.set_output_pattern(&output_pattern);
+
+                if !issues.is_empty() {
+                    let title = lang::tr("ui-lint-title");
+                    nwg::modal_info_message(&self.window, &title, &lint_text(&issues));
+                }
             }
             Err(err) => {
                 let err_msg = self.failed_load_str();
@@ -144,8 +151,8 @@ impl FillerApp {
 
     /// Triggers batch generation of DOCX files from input data.
     fn generate_docxs(&self) {
-        let docx_ref = self.opened_docx.borrow();
-        let generator = match &*docx_ref {
+        let mut docx_ref = self.opened_docx.borrow_mut();
+        let generator = match &mut *docx_ref {
             Some(docx) => docx,
             None => {
                 let title = lang::tr("ui-docx-failure");
@@ -159,6 +166,10 @@ impl FillerApp {
         let text = self.values_partial.get_values_text();
         let separator = self.options_partial.get_separator();
         let output_pattern = self.output_partial.output_pattern();
+        generator.set_fill_mode(self.options_partial.get_fill_mode());
+        generator.set_batch_output(self.output_partial.get_batch_output());
+        generator.set_batch_mode(self.output_partial.get_batch_mode());
+        generator.set_workers(self.options_partial.get_workers());
 
         if let Err(err) = generator.build_docx_batch(&tokens, &text, &separator, &output_pattern) {
             let err_msg = self.failed_load_str();
@@ -170,6 +181,31 @@ impl FillerApp {
         }
     }
 
+    /// Shows the report on tokens of the opened template - where each of them is used in the document.
+    fn show_token_report(&self) {
+        let docx_ref = self.opened_docx.borrow();
+        let docx = match &*docx_ref {
+            Some(docx) => docx,
+            None => {
+                let title = lang::tr("ui-docx-failure");
+                let content = lang::tr("ui-docx-no-template");
+                nwg::modal_info_message(&self.window, &title, &content);
+                return;
+            }
+        };
+
+        match docx.token_report() {
+            Ok(reports) => {
+                let title = lang::tr("ui-report-title");
+                nwg::modal_info_message(&self.window, &title, &report_text(&reports));
+            }
+            Err(err) => {
+                let err_msg = self.failed_load_str();
+                nwg::modal_error_message(&self.window, &err_msg, &err.to_string());
+            }
+        }
+    }
+
     /// Invoke language change from the "options" partial...
     fn set_lang(&self) {
         if let Err(msg) = self.options_partial.set_current_lang() {
@@ -196,3 +232,66 @@ impl FillerApp {
         nwg::stop_thread_dispatch();
     }
 }
+
+/// Writes out the report on tokens as a text - each token with its occurrences count,
+/// followed by the locations of the occurrences, one per line.
+fn report_text(reports: &[TokenReport]) -> String {
+    let mut lines: Vec<String> = Default::default();
+    for report in reports {
+        let args: lang::TrArgVec = vec![
+            ("token".to_string(), report.token.to_string()),
+            ("count".to_string(), report.count().to_string()),
+        ];
+        lines.push(lang::tr_with_args("ui-report-token", &args));
+        for occurrence in report.occurrences.iter() {
+            lines.push(location_text(occurrence));
+        }
+    }
+    lines.join("\n")
+}
+
+/// Writes out the problems found in the template by the linter as a text - each problem on one line,
+/// followed by its location.
+fn lint_text(issues: &[LintIssue]) -> String {
+    let mut lines: Vec<String> = Default::default();
+    for issue in issues {
+        let mut args: lang::TrArgVec = vec![("token".to_string(), issue.text.to_string())];
+        let msg_id = match &issue.kind {
+            LintKind::Unclosed => "ui-lint-unclosed",
+            LintKind::Unopened => "ui-lint-unopened",
+            LintKind::SplitByMarkup => "ui-lint-split",
+            LintKind::MarkupInside => "ui-lint-markup",
+            LintKind::TooLong => "ui-lint-long",
+            LintKind::NearDuplicate(similar) => {
+                args.push(("similar".to_string(), similar.to_string()));
+                "ui-lint-similar"
+            }
+            LintKind::ConflictingDefault(first) => {
+                args.push(("default".to_string(), first.to_string()));
+                "ui-lint-default"
+            }
+        };
+        lines.push(lang::tr_with_args(msg_id, &args));
+        lines.push(location_text(&issue.occurrence));
+    }
+    lines.join("\n")
+}
+
+/// Writes out the location of the token occurrence (part, paragraph and placement), with the text around it.
+fn location_text(occurrence: &docx_filler::TokenOccurrence) -> String {
+    let mut location = vec![occurrence.part.to_string()];
+    if let Some(paragraph) = occurrence.paragraph {
+        let args: lang::TrArgVec = vec![("paragraph".to_string(), paragraph.to_string())];
+        location.push(lang::tr_with_args("ui-report-paragraph", &args));
+    }
+    if occurrence.in_header {
+        location.push(lang::tr("ui-report-header"));
+    }
+    if occurrence.in_table {
+        location.push(lang::tr("ui-report-table"));
+    }
+    if occurrence.in_text_box {
+        location.push(lang::tr("ui-report-text-box"));
+    }
+    format!("    {}: \"{}\"", location.join(", "), occurrence.context)
+}
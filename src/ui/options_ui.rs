@@ -1,12 +1,13 @@
 use super::consts;
+use crate::docx_filler::{FillMode, TemplateOptions, TokenNormalization};
 use crate::lang;
 use nwd::NwgPartial;
 use nwg::stretch::geometry::Size;
-use nwg::stretch::style::{AlignItems, Dimension, FlexDirection};
+use nwg::stretch::style::{AlignContent, AlignItems, Dimension, FlexDirection, FlexWrap};
 
 #[derive(Default, NwgPartial)]
 pub struct OptionsUi {
-    #[nwg_layout(flex_direction: FlexDirection::Row, align_items: AlignItems::Center)]
+    #[nwg_layout(flex_direction: FlexDirection::Row, flex_wrap: FlexWrap::Wrap, align_items: AlignItems::Center, align_content: AlignContent::FlexStart)]
     layout: nwg::FlexboxLayout,
 
     #[nwg_control(text: "\u{2328}:", h_align: HTextAlign::Right)]
@@ -29,6 +30,42 @@ pub struct OptionsUi {
     #[nwg_control(text: ";")]
     #[nwg_layout_item(layout: layout, size: Size { width: Dimension::Points(100.0), height: consts::INPUT_HEIGHT })]
     separator_input: nwg::TextInput,
+
+    #[nwg_control(text: &lang::tr("ui-options-delimiters-label"), h_align: HTextAlign::Right)]
+    #[nwg_layout_item(layout: layout, flex_grow: 1.0, min_size: Size { width: Dimension::Points(140.0), height: consts::INPUT_HEIGHT })]
+    delimiters_label: nwg::Label,
+
+    #[nwg_control(text: "{{")]
+    #[nwg_layout_item(layout: layout, size: Size { width: Dimension::Points(50.0), height: consts::INPUT_HEIGHT })]
+    open_delimiter_input: nwg::TextInput,
+
+    #[nwg_control(text: "}}")]
+    #[nwg_layout_item(layout: layout, size: Size { width: Dimension::Points(50.0), height: consts::INPUT_HEIGHT })]
+    close_delimiter_input: nwg::TextInput,
+
+    #[nwg_control(text: &lang::tr("ui-options-workers-label"), h_align: HTextAlign::Right)]
+    #[nwg_layout_item(layout: layout, flex_grow: 1.0, min_size: Size { width: Dimension::Points(140.0), height: consts::INPUT_HEIGHT })]
+    workers_label: nwg::Label,
+
+    #[nwg_control(text: "")]
+    #[nwg_layout_item(layout: layout, size: Size { width: Dimension::Points(50.0), height: consts::INPUT_HEIGHT })]
+    workers_input: nwg::TextInput,
+
+    #[nwg_control(text: &lang::tr("ui-options-partial-check"))]
+    #[nwg_layout_item(layout: layout, size: Size { width: Dimension::Points(180.0), height: consts::INPUT_HEIGHT })]
+    partial_check: nwg::CheckBox,
+
+    #[nwg_control(text: &lang::tr("ui-options-remove-controls-check"))]
+    #[nwg_layout_item(layout: layout, size: Size { width: Dimension::Points(180.0), height: consts::INPUT_HEIGHT })]
+    remove_controls_check: nwg::CheckBox,
+
+    #[nwg_control(text: &lang::tr("ui-options-ignore-case-check"))]
+    #[nwg_layout_item(layout: layout, size: Size { width: Dimension::Points(180.0), height: consts::INPUT_HEIGHT })]
+    ignore_case_check: nwg::CheckBox,
+
+    #[nwg_control(text: &lang::tr("ui-options-unicode-check"))]
+    #[nwg_layout_item(layout: layout, size: Size { width: Dimension::Points(180.0), height: consts::INPUT_HEIGHT })]
+    unicode_check: nwg::CheckBox,
 }
 
 impl OptionsUi {
@@ -37,6 +74,36 @@ impl OptionsUi {
         self.separator_input.text()
     }
 
+    /// Gets options for loading the template - currently configured token delimiters,
+    /// whether the content controls are to be removed, and how the token names are compared.
+    pub fn get_template_options(&self) -> TemplateOptions {
+        TemplateOptions {
+            open_delimiter: self.open_delimiter_input.text(),
+            close_delimiter: self.close_delimiter_input.text(),
+            remove_controls: self.remove_controls_check.check_state()
+                == nwg::CheckBoxState::Checked,
+            normalization: TokenNormalization {
+                trim_whitespace: true,
+                ignore_case: self.ignore_case_check.check_state() == nwg::CheckBoxState::Checked,
+                unicode_form: self.unicode_check.check_state() == nwg::CheckBoxState::Checked,
+            },
+        }
+    }
+
+    /// Gets currently selected mode of filling the tokens - partial one keeps the tokens without values.
+    pub fn get_fill_mode(&self) -> FillMode {
+        match self.partial_check.check_state() {
+            nwg::CheckBoxState::Checked => FillMode::Partial,
+            _ => FillMode::Complete,
+        }
+    }
+
+    /// Gets currently configured number of documents generated in parallel - 0 (one per each CPU core)
+    /// when it is not set.
+    pub fn get_workers(&self) -> usize {
+        self.workers_input.text().trim().parse().unwrap_or(0)
+    }
+
     pub fn set_current_lang(&self) -> Result<(), String> {
         if let Some(new_lang_str) = self.lang_dropdown.selection_string() {
             if let Some(new_lang) = lang::SupportedLanguage::from_string(&new_lang_str) {
@@ -50,6 +117,18 @@ impl OptionsUi {
     pub fn reset_language(&self) {
         self.separator_label
             .set_text(&lang::tr("ui-options-sep-label"));
+        self.delimiters_label
+            .set_text(&lang::tr("ui-options-delimiters-label"));
+        self.partial_check
+            .set_text(&lang::tr("ui-options-partial-check"));
+        self.remove_controls_check
+            .set_text(&lang::tr("ui-options-remove-controls-check"));
+        self.ignore_case_check
+            .set_text(&lang::tr("ui-options-ignore-case-check"));
+        self.unicode_check
+            .set_text(&lang::tr("ui-options-unicode-check"));
+        self.workers_label
+            .set_text(&lang::tr("ui-options-workers-label"));
     }
 }
 
@@ -1,4 +1,5 @@
 use super::consts;
+use crate::docx_filler::{BatchMode, BatchOutput};
 use crate::lang::tr;
 use nwd::NwgPartial;
 use nwg::stretch::geometry::Size;
@@ -17,6 +18,14 @@ pub struct OutputUi {
     #[nwg_layout_item(layout: layout, flex_grow: 1.0, size: Size { width: Dimension::Auto, height: consts::INPUT_HEIGHT })]
     input: nwg::TextInput,
 
+    #[nwg_control(text: &tr("ui-output-combine-check"))]
+    #[nwg_layout_item(layout: layout, size: Size { width: Dimension::Points(180.0), height: consts::INPUT_HEIGHT })]
+    combine_check: nwg::CheckBox,
+
+    #[nwg_control(text: &tr("ui-output-transactional-check"))]
+    #[nwg_layout_item(layout: layout, size: Size { width: Dimension::Points(180.0), height: consts::INPUT_HEIGHT })]
+    transactional_check: nwg::CheckBox,
+
     #[nwg_control(text: &tr("ui-output-button"))]
     #[nwg_layout_item(layout: layout, size: Size { width: Dimension::Points(180.0), height: consts::BUTTON_HEIGHT })]
     pub button: nwg::Button,
@@ -31,8 +40,27 @@ impl OutputUi {
         self.input.set_text(pattern);
     }
 
+    /// Gets currently selected output of the batch - combined one puts all the documents into a single file.
+    pub fn get_batch_output(&self) -> BatchOutput {
+        match self.combine_check.check_state() {
+            nwg::CheckBoxState::Checked => BatchOutput::Combined,
+            _ => BatchOutput::Separate,
+        }
+    }
+
+    /// Gets currently selected mode of writing the batch - transactional one keeps no document on a failure.
+    pub fn get_batch_mode(&self) -> BatchMode {
+        match self.transactional_check.check_state() {
+            nwg::CheckBoxState::Checked => BatchMode::Transactional,
+            _ => BatchMode::Progressive,
+        }
+    }
+
     pub fn reset_language(&self) {
         self.label.set_text(&tr("ui-output-label"));
+        self.combine_check.set_text(&tr("ui-output-combine-check"));
+        self.transactional_check
+            .set_text(&tr("ui-output-transactional-check"));
         self.button.set_text(&tr("ui-output-button"));
     }
 }
@@ -24,6 +24,10 @@ pub struct TemplateUi {
     #[nwg_control(text: &tr("ui-template-button"))]
     #[nwg_layout_item(layout: layout, size: Size { width: Dimension::Points(180.0), height: consts::BUTTON_HEIGHT })]
     pub button: nwg::Button,
+
+    #[nwg_control(text: &tr("ui-template-report-button"))]
+    #[nwg_layout_item(layout: layout, size: Size { width: Dimension::Points(180.0), height: consts::BUTTON_HEIGHT })]
+    pub report_button: nwg::Button,
 }
 
 impl TemplateUi {
@@ -52,5 +56,7 @@ impl TemplateUi {
         self.label.set_text(&tr("ui-template-label"));
         self.dialog.set_title(&tr("ui-template-dialog"));
         self.button.set_text(&tr("ui-template-button"));
+        self.report_button
+            .set_text(&tr("ui-template-report-button"));
     }
 }